@@ -0,0 +1,7 @@
+mod graph_builder;
+mod module_graph;
+mod scan_imports;
+
+pub use crate::graph::graph_builder::*;
+pub use crate::graph::module_graph::*;
+pub use crate::graph::scan_imports::*;
@@ -0,0 +1,39 @@
+/*
+  The modules reachable from a set of entries and the imports
+  between them. Modules are referred to by their index in "nodes"
+*/
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::public::ImportKind;
+use crate::public::ResolvedId;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModuleGraph {
+  pub nodes: Vec<GraphNode>,
+  pub edges: Vec<GraphEdge>,
+  pub errors: Vec<GraphError>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphNode {
+  pub id: ResolvedId,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphEdge {
+  pub from: usize,
+  // None for externals and builtins, they are not part of the graph
+  pub to: Option<usize>,
+  pub specifier: String,
+  pub kind: ImportKind,
+}
+
+// A specifier that could not be resolved, or a module that could not
+// be read when there is no specifier
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphError {
+  pub from: usize,
+  pub specifier: Option<String>,
+  pub message: String,
+}
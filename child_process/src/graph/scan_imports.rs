@@ -0,0 +1,282 @@
+/*
+  Finds the specifiers a module imports without parsing it:
+    import x from "a"     import "a"     export { x } from "a"
+    require("a")          import("a")
+
+  Comments and strings are skipped so commented out imports are not
+  picked up. Regular expression literals are not recognized, a quote
+  inside of one can throw the scan off for the rest of the module
+*/
+use crate::public::Dependency;
+use crate::public::ImportKind;
+
+pub fn scan_imports(code: &str) -> Vec<Dependency> {
+  let mut scanner = Scanner {
+    code,
+    bytes: code.as_bytes(),
+    pos: 0,
+  };
+  let mut dependencies = vec![];
+
+  while let Some(byte) = scanner.peek() {
+    match byte {
+      b'/' if scanner.peek_at(1) == Some(b'/') => scanner.skip_until("\n"),
+      b'/' if scanner.peek_at(1) == Some(b'*') => scanner.skip_until("*/"),
+      b'\'' | b'"' | b'`' => {
+        scanner.string();
+      }
+      byte if is_word_byte(byte) => {
+        // Skips over methods, e.g. "module.require("
+        let is_property = scanner.pos > 0 && scanner.bytes[scanner.pos - 1] == b'.';
+        let dependency = match scanner.word() {
+          _ if is_property => None,
+          "import" => scanner.import(),
+          "export" => scanner.export(),
+          "require" => scanner.call(ImportKind::Require),
+          _ => None,
+        };
+        dependencies.extend(dependency);
+      }
+      _ => scanner.pos += 1,
+    }
+  }
+
+  return dependencies;
+}
+
+struct Scanner<'a> {
+  code: &'a str,
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+  fn peek(&self) -> Option<u8> {
+    self.peek_at(0)
+  }
+
+  fn peek_at(
+    &self,
+    offset: usize,
+  ) -> Option<u8> {
+    self.bytes.get(self.pos + offset).copied()
+  }
+
+  // Moves past the next occurrence of "end", or to the end of the code
+  fn skip_until(
+    &mut self,
+    end: &str,
+  ) {
+    self.pos = match self.code[self.pos..].find(end) {
+      Some(index) => self.pos + index + end.len(),
+      None => self.bytes.len(),
+    };
+  }
+
+  fn skip_whitespace(&mut self) {
+    while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+      self.pos += 1;
+    }
+  }
+
+  fn word(&mut self) -> &'a str {
+    let start = self.pos;
+    while self.peek().is_some_and(is_word_byte) {
+      self.pos += 1;
+    }
+    return &self.code[start..self.pos];
+  }
+
+  // Reads the string literal at the current position, None for
+  // template literals with substitutions
+  fn string(&mut self) -> Option<String> {
+    let quote = self.peek()?;
+    let start = self.pos + 1;
+    self.pos += 1;
+
+    while let Some(byte) = self.peek() {
+      self.pos += 1;
+      if byte == b'\\' {
+        self.pos += 1;
+      } else if byte == quote {
+        let value = &self.code[start..self.pos - 1];
+        if quote == b'`' && value.contains("${") {
+          return None;
+        }
+        return Some(value.to_string());
+      }
+    }
+    return None;
+  }
+
+  // ("specifier")
+  fn call(
+    &mut self,
+    kind: ImportKind,
+  ) -> Option<Dependency> {
+    self.skip_whitespace();
+    if self.peek()? != b'(' {
+      return None;
+    }
+    self.pos += 1;
+    self.skip_whitespace();
+    if !is_quote(self.peek()?) {
+      return None;
+    }
+    let specifier = self.string()?;
+    self.skip_whitespace();
+    if self.peek()? != b')' {
+      return None;
+    }
+
+    return Some(Dependency { specifier, kind });
+  }
+
+  fn import(&mut self) -> Option<Dependency> {
+    self.skip_whitespace();
+    match self.peek()? {
+      b'(' => return self.call(ImportKind::DynamicImport),
+      // import.meta
+      b'.' => return None,
+      _ => {}
+    }
+
+    // The specifier is the first string, after any bindings
+    while let Some(byte) = self.peek() {
+      if is_quote(byte) {
+        let specifier = self.string()?;
+        return Some(Dependency {
+          specifier,
+          kind: ImportKind::Import,
+        });
+      }
+      if byte == b';' || byte == b'(' {
+        return None;
+      }
+      self.pos += 1;
+    }
+    return None;
+  }
+
+  // Only re-exports import anything, "export * from" and "export { } from"
+  fn export(&mut self) -> Option<Dependency> {
+    self.skip_whitespace();
+    match self.peek()? {
+      b'*' => {
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.word() == "as" {
+          self.skip_whitespace();
+          self.word();
+        }
+      }
+      b'{' => self.skip_until("}"),
+      _ => return None,
+    }
+
+    self.skip_whitespace();
+    if self.word() != "from" {
+      return None;
+    }
+    self.skip_whitespace();
+    if !is_quote(self.peek()?) {
+      return None;
+    }
+    let specifier = self.string()?;
+
+    return Some(Dependency {
+      specifier,
+      kind: ImportKind::Import,
+    });
+  }
+}
+
+fn is_word_byte(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'$'
+}
+
+fn is_quote(byte: u8) -> bool {
+  byte == b'\'' || byte == b'"' || byte == b'`'
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn specifiers(code: &str) -> Vec<(String, ImportKind)> {
+    return scan_imports(code)
+      .into_iter()
+      .map(|dependency| (dependency.specifier, dependency.kind))
+      .collect();
+  }
+
+  #[test]
+  fn finds_every_kind_of_import() {
+    let code = r#"
+      import x from "a"
+      import { y } from 'b'
+      import "c"
+      export * as z from "d"
+      export { w } from "e"
+      const f = require("f")
+      const g = await import('g')
+    "#;
+    assert_eq!(
+      specifiers(code),
+      vec![
+        ("a".to_string(), ImportKind::Import),
+        ("b".to_string(), ImportKind::Import),
+        ("c".to_string(), ImportKind::Import),
+        ("d".to_string(), ImportKind::Import),
+        ("e".to_string(), ImportKind::Import),
+        ("f".to_string(), ImportKind::Require),
+        ("g".to_string(), ImportKind::DynamicImport),
+      ]
+    );
+  }
+
+  #[test]
+  fn skips_comments() {
+    let code = r#"
+      // import "line"
+      /* require("block") */
+      /*
+        import "multiline"
+      */
+      import "kept" // import "trailing"
+    "#;
+    assert_eq!(
+      specifiers(code),
+      vec![("kept".to_string(), ImportKind::Import)]
+    );
+  }
+
+  #[test]
+  fn skips_strings_and_template_literals() {
+    let code = r#"
+      const a = "require('double')"
+      const b = 'import "single"'
+      const c = `import("template")`
+      const d = "escaped \" require('quote')"
+      const e = `${require("substitution")}`
+      require("kept")
+    "#;
+    assert_eq!(
+      specifiers(code),
+      vec![("kept".to_string(), ImportKind::Require)]
+    );
+  }
+
+  #[test]
+  fn skips_what_only_looks_like_an_import() {
+    let code = r#"
+      module.require("method")
+      import.meta.url
+      import(`./${name}.js`)
+      require(name)
+      export const value = "export"
+      const myrequire = require
+    "#;
+    assert_eq!(specifiers(code), vec![]);
+  }
+}
@@ -1,35 +1,182 @@
+mod config;
+mod graph;
 mod node_adapter;
+mod pipeline;
 mod plugins;
 mod public;
+mod watcher;
 
 use std::env;
+use std::path::Path;
+use std::process;
 use std::sync::Arc;
+use std::thread;
 
+use config::Backend;
+use config::MachConfig;
+use config::PluginSet;
+use graph::GraphBuilder;
+use node_adapter::LifecycleHook;
 use node_adapter::NodeInstance;
-use plugins::DefaultResolver;
-use plugins::ResolverNodeProxy;
-use public::Resolver;
+use pipeline::DiskCache;
+use public::Loader;
+use public::Reporter;
+use public::ReporterEvent;
+use public::ResolveOptions;
+use public::TransformInput;
+use public::Transformer;
+use watcher::FileWatcher;
 
 fn main() {
   // Parse CLI args
   let args: Vec<String> = env::args().collect();
-  let node_worker_count = args.get(1).unwrap_or(&"4".to_string()).parse::<usize>().unwrap();
+  let concurrent = args.iter().any(|arg| arg == "--concurrent");
+  let watch = args.iter().any(|arg| arg == "--watch");
+
+  // Plugins and the worker count come from the closest mach.config.json
+  let config = MachConfig::find(&env::current_dir().unwrap())
+    .and_then(|config| config.check_backend(Backend::ChildProcess).map(|_| config));
+  let config = match config {
+    Ok(config) => config,
+    Err(error) => {
+      eprintln!("{}", error);
+      process::exit(1);
+    }
+  };
+  let node_worker_count = match args.get(1) {
+    Some(arg) => arg.parse::<usize>().unwrap(),
+    None => config.workers.unwrap_or(4),
+  };
 
   // Create a Node.js child process, spawn worker threads within it and connect to them
   let node_instance = Arc::new(NodeInstance::new(node_worker_count));
 
-  // Mimic loading plugins in from config
-  let mut resolvers = Vec::<Box<dyn Resolver>>::new();
-  resolvers.push(Box::new(DefaultResolver::new()));
-  resolvers.push(Box::new(ResolverNodeProxy::new(node_instance.clone(), "../plugin")));
+  let plugins = match PluginSet::load(&config, &node_instance, concurrent) {
+    Ok(plugins) => plugins,
+    Err(error) => {
+      eprintln!("{}", error);
+      process::exit(1);
+    }
+  };
+  let resolvers = &plugins.resolvers;
+
+  // Reuse resolutions from previous runs
+  let cache_dir = env::current_dir().unwrap().join(".mach").join("cache");
+  let disk_cache = DiskCache::new(&cache_dir);
+  disk_cache.load(resolvers);
 
   // Mimic running resolvers
   let from_path = env::current_dir().unwrap();
+  let options = ResolveOptions::default();
+
+  // Mimic a build of the example project
+  let entries = vec![from_path.parent().unwrap().join("example").join("main.js")];
+  let graph_builder = GraphBuilder::new(resolvers, options.clone());
+
+  // Plugins hear about builds through their lifecycle hooks
+  // as well as the reporter events
+  let run_hook = |hook: LifecycleHook| {
+    for message in node_instance.run_hook(hook) {
+      plugins.report(&ReporterEvent::Warning { message, module: None });
+    }
+  };
+
+  let run = || {
+    run_hook(LifecycleHook::BuildStart);
+    plugins.report(&ReporterEvent::BuildStart);
+
+    let graph = graph_builder.build(&entries);
+    println!("graph: {}", serde_json::to_string_pretty(&graph).unwrap());
+
+    for (completed, node) in graph.nodes.iter().enumerate() {
+      plugins.report(&ReporterEvent::Progress {
+        module: node.id.to_string(),
+        completed: completed + 1,
+      });
+    }
+
+    println!("cache: {:?}", resolvers.cache().stats());
+
+    // Failing to write the cache only makes the next run slower
+    if let Err(error) = disk_cache.save(resolvers) {
+      plugins.report(&ReporterEvent::Warning {
+        message: format!("Could not write the cache: {}", error),
+        module: None,
+      });
+    }
+
+    run_hook(LifecycleHook::BuildEnd);
+    plugins.report(&ReporterEvent::BuildEnd {
+      success: graph.errors.is_empty(),
+    });
+  };
 
-  for resolver in &resolvers {
-    let Some(file_path) = resolver.resolve(&from_path, "hi") else {
-      continue;
+  run();
+
+  // Mimic running transformers
+  let input = TransformInput {
+    file_path: from_path.join("index.js"),
+    code: "import hi from 'hi';\n".to_string(),
+    ..Default::default()
+  };
+  match plugins.transform(&input) {
+    Ok(output) => println!("transformed: {:?}", output),
+    Err(error) => println!("error: {}", error),
+  }
+
+  // Mimic loading a virtual module
+  let resolved_id = resolvers
+    .resolve(&from_path, "virtual:routes", &options)
+    .map(|entry| entry.result.resolution.resolved_id());
+  match resolved_id {
+    Ok(Some(resolved_id)) => match plugins.load(&resolved_id) {
+      Ok(output) => println!("loaded {}: {:?}", resolved_id, output),
+      Err(error) => println!("error: {}", error),
+    },
+    Ok(None) => println!("unresolved: virtual:routes"),
+    Err(error) => println!("error: {}", error),
+  }
+
+  if watch {
+    // The example project and the config (with its plugins) can change,
+    // build output and the cache are written while watching
+    let config_dir = env::current_dir().unwrap().join(config.config_path());
+    let roots = [
+      from_path.parent().unwrap().join("example"),
+      config_dir.parent().unwrap().to_path_buf(),
+    ];
+    let ignored = |path: &Path| {
+      path.starts_with(&cache_dir) || path.components().any(|part| part.as_os_str() == "target")
     };
-    println!("resolved: {:?}", file_path);
+
+    let watcher = FileWatcher::new(&roots).unwrap();
+    let on_events = watcher.subscribe();
+
+    // Ctrl-C drops the watcher, which ends the loop below so the
+    // plugins are torn down before exiting
+    thread::spawn(move || {
+      let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+      runtime.block_on(tokio::signal::ctrl_c()).ok();
+      drop(watcher);
+    });
+
+    // Run again whenever files change, only what depended
+    // on the changed files is resolved again
+    while let Ok(mut events) = on_events.recv() {
+      events.extend(on_events.try_iter().flatten());
+      events.retain(|event| !ignored(event.path()));
+      if events.is_empty() {
+        continue;
+      }
+      resolvers.invalidate(&events);
+      run();
+    }
+  }
+
+  for error in node_instance.close() {
+    eprintln!("{}", error);
   }
 }
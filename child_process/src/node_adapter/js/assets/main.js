@@ -5,6 +5,11 @@
 */
 const { Worker } = require('node:worker_threads');
 
+// Ctrl-C in a terminal reaches this process as well, the host decides
+// when to stop and runs the teardown hooks first. The workers exit once
+// their connection to the host closes
+process.on('SIGINT', () => {})
+
 const worker_code = `__MACH_WORKER_SCRIPT__`
 const worker_count = parseInt('__MACH_WORKER_COUNT__', 10)
 
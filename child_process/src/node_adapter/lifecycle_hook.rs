@@ -0,0 +1,41 @@
+/*
+  The lifecycle hooks JS plugins may export next to their main
+  function. setup(ctx) runs as part of loading a plugin, the rest
+  are run on every worker with NodeInstance::run_hook
+*/
+use std::fmt::Display;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleHook {
+  BuildStart,
+  BuildEnd,
+  Teardown,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunHookRequest {
+  pub hook: LifecycleHook,
+}
+
+// Each worker sends back the errors its plugins threw
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RunHookResponse {
+  pub errors: Vec<String>,
+}
+
+impl Display for LifecycleHook {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      LifecycleHook::BuildStart => write!(f, "buildStart"),
+      LifecycleHook::BuildEnd => write!(f, "buildEnd"),
+      LifecycleHook::Teardown => write!(f, "teardown"),
+    }
+  }
+}
@@ -1,7 +1,9 @@
 mod js;
+mod lifecycle_hook;
 mod node_instance;
 mod node_worker;
 mod spawn;
 
+pub use crate::node_adapter::lifecycle_hook::*;
 pub use crate::node_adapter::node_instance::*;
 pub use crate::node_adapter::node_worker::*;
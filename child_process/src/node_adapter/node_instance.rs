@@ -8,6 +8,13 @@
   a demo.
 
   Messages are load balanced between Node workers using round robin.
+  Notifications (notify/notify_all) are one-way, they are not
+  tracked in pending_messages and the worker does not respond.
+
+  Plugins loaded into the workers are referred to by an id handed
+  out here, so the same plugin can be loaded more than once. Their
+  lifecycle hooks are run on every worker with run_hook, close runs
+  the teardown hooks before Node.js is stopped.
 
   The glue code that is run within Node.js is piped in via stdin, 
   but a final implementation would probably ship the JS glue code 
@@ -20,6 +27,8 @@ use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Write;
 use std::net::TcpListener;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
@@ -32,12 +41,17 @@ use serde::Serialize;
 use tokio::sync::oneshot;
 
 use super::spawn::spawn_node_js;
+use super::LifecycleHook;
+use super::NodeError;
 use super::NodeResponse;
 use super::NodeWorker;
+use super::RunHookRequest;
+use super::RunHookResponse;
 
 #[derive(Debug)]
 pub struct NodeInstance {
   send_to: Arc<Mutex<usize>>,
+  next_plugin_id: AtomicUsize,
   tx_shutdown: Sender<()>,
   workers: Vec<NodeWorker>,
 }
@@ -96,7 +110,7 @@ impl NodeInstance {
           line.pop();
           let value = std::mem::take(&mut line);
 
-          if incoming_msg_ref == "" {
+          if incoming_msg_ref.is_empty() {
             incoming_msg_ref = value;
             continue;
           }
@@ -135,11 +149,17 @@ impl NodeInstance {
 
     return NodeInstance {
       send_to: Arc::new(Mutex::new(0)),
+      next_plugin_id: AtomicUsize::new(0),
       tx_shutdown,
       workers,
     };
   }
 
+  // For a plugin about to be loaded into the workers, the same on every worker
+  pub fn new_plugin_id(&self) -> usize {
+    return self.next_plugin_id.fetch_add(1, Ordering::Relaxed);
+  }
+
   pub fn send<T>(
     &self,
     action: &str,
@@ -156,7 +176,7 @@ impl NodeInstance {
         *send_to = 0;
       }
 
-      let send_index = (*send_to).clone();
+      let send_index = *send_to;
       *send_to += 1;
       send_index
     };
@@ -164,35 +184,36 @@ impl NodeInstance {
     self.workers[send_to].send(action, data)
   }
 
-  pub fn send_all<T>(
+  pub fn send_all<T, U>(
     &self,
     action: &str,
     data: &T,
-  ) -> Result<(), ()>
+  ) -> Result<Vec<U>, NodeError>
   where
     T: ?Sized + Serialize,
+    U: DeserializeOwned,
   {
-    let mut responses = vec![];
+    let mut on_responses = vec![];
 
     for worker in &self.workers {
       let on_response = worker.send(action, data);
-      responses.push(on_response);
+      on_responses.push(on_response);
     }
 
-    for response in &mut responses {
-      if response.recv_void().is_err() {
-        return Err(());
-      }
+    let mut responses = vec![];
+
+    for on_response in &mut on_responses {
+      responses.push(on_response.recv()?);
     }
 
-    return Ok(());
+    return Ok(responses);
   }
 
   pub fn send_blocking<T, U>(
     &self,
     action: &str,
     data: &T,
-  ) -> Result<U, ()>
+  ) -> Result<U, NodeError>
   where
     T: ?Sized + Serialize,
     U: DeserializeOwned,
@@ -201,6 +222,65 @@ impl NodeInstance {
     return response.recv();
   }
 
+  // Sends to the given worker without waiting for it, the worker
+  // handles these in the order they were sent. Fails when there is
+  // no such worker
+  pub fn notify<T>(
+    &self,
+    worker: usize,
+    action: &str,
+    data: &T,
+  ) -> Result<(), ()>
+  where
+    T: ?Sized + Serialize,
+  {
+    let Some(worker) = self.workers.get(worker) else {
+      return Err(());
+    };
+    worker.notify(action, data);
+    return Ok(());
+  }
+
+  // Sends to every worker without waiting for them, fails when
+  // there are no workers
+  pub fn notify_all<T>(
+    &self,
+    action: &str,
+    data: &T,
+  ) -> Result<(), ()>
+  where
+    T: ?Sized + Serialize,
+  {
+    if self.workers.is_empty() {
+      return Err(());
+    }
+    for worker in &self.workers {
+      worker.notify(action, data);
+    }
+    return Ok(());
+  }
+
+  // Runs the hook of every plugin on every worker and waits for them
+  // to finish, returns the errors the hooks threw
+  pub fn run_hook(
+    &self,
+    hook: LifecycleHook,
+  ) -> Vec<String> {
+    let req = RunHookRequest { hook };
+    let Ok(responses) = self.send_all::<_, RunHookResponse>("run_hook", &req) else {
+      return vec![format!("No response from the workers running {}", hook)];
+    };
+    return responses.into_iter().flat_map(|response| response.errors).collect();
+  }
+
+  // Lets plugins clean up before stopping Node.js, dropping the
+  // instance stops it without running the teardown hooks
+  pub fn close(&self) -> Vec<String> {
+    let errors = self.run_hook(LifecycleHook::Teardown);
+    self.shutdown().ok();
+    return errors;
+  }
+
   pub fn shutdown(&self) -> Result<(), ()> {
     if self.tx_shutdown.send(()).is_err() {
       return Err(());
@@ -18,7 +18,25 @@ pub struct NodeWorker {
   pub pending_messages: Arc<Mutex<HashMap<String, tokio::sync::oneshot::Sender<String>>>>,
 }
 
+// Sent in place of the message_ref for messages that get no response
+pub const NOTIFY_REF: &str = "-";
+
 impl NodeWorker {
+  // Sends without waiting, nothing comes back from the worker
+  pub fn notify<T>(
+    &self,
+    action: &str,
+    data: &T,
+  ) where
+    T: ?Sized + Serialize,
+  {
+    let data = serde_json::to_string::<T>(data).unwrap();
+    self
+      .tx_to_child
+      .send((NOTIFY_REF.to_string(), action.to_string(), data))
+      .ok();
+  }
+
   pub fn send<T>(
     &self,
     action: &str,
@@ -46,30 +64,39 @@ pub struct NodeResponse {
   rx: Option<oneshot::Receiver<String>>,
 }
 
+// Why a request sent to a worker has no usable response
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeError {
+  // The worker went away before it responded
+  NoResponse,
+  // The response doesn't have the expected shape, with why it couldn't be read
+  InvalidResponse(String),
+}
+
 impl NodeResponse {
-  pub fn recv<T>(&mut self) -> Result<T, ()>
+  pub fn recv<T>(&mut self) -> Result<T, NodeError>
   where
     T: DeserializeOwned,
   {
     let Some(rx) = self.rx.take() else {
-      return Err(());
+      return Err(NodeError::NoResponse);
     };
     let Ok(value) = rx.blocking_recv() else {
-      return Err(());
+      return Err(NodeError::NoResponse);
     };
-    let data = serde_json::from_str::<T>(&value).unwrap();
-
-    return Ok(data);
+    return serde_json::from_str::<T>(&value)
+      .map_err(|error| NodeError::InvalidResponse(error.to_string()));
   }
+}
 
-  pub fn recv_void(&mut self) -> Result<(), ()> {
-    let Some(rx) = self.rx.take() else {
-      return Err(());
-    };
-    let Ok(_) = rx.blocking_recv() else {
-      return Err(());
-    };
-
-    return Ok(());
+impl std::fmt::Display for NodeError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      NodeError::NoResponse => write!(f, "No response"),
+      NodeError::InvalidResponse(error) => write!(f, "Invalid response: {}", error),
+    }
   }
 }
@@ -19,7 +19,7 @@ pub fn spawn_node_js(port: &u16, worker_count: &usize) -> Child {
   // Execute the glue code within Node.js
   let mut stdin = child.stdin.take().unwrap();
   let script = get_js(port, worker_count);
-  stdin.write(script.as_bytes()).unwrap();
+  stdin.write_all(script.as_bytes()).unwrap();
   drop(stdin);
 
   return child;
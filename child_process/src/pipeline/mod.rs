@@ -0,0 +1,9 @@
+mod disk_cache;
+mod resolve_cache;
+mod resolver_pipeline;
+mod thread_pool;
+
+pub use crate::pipeline::disk_cache::*;
+pub use crate::pipeline::resolve_cache::*;
+pub use crate::pipeline::resolver_pipeline::*;
+pub use crate::pipeline::thread_pool::*;
@@ -0,0 +1,385 @@
+/*
+  Caches the answers of the resolver pipeline so that the same
+  specifier imported from the same directory is only resolved once
+  (saving a round trip to Node.js for every hit).
+
+  Entries are keyed by the directory of the importing file, the
+  specifier and the resolve options. Each entry is indexed by the
+  files it depends on, as reported by the resolvers:
+    - invalidate_on_file_change (and the resolved file itself)
+      drop the entry when that file changes or is deleted
+    - invalidate_on_file_create drop the entry when that file
+      is created
+    - creating a file next to the resolved file with the same name
+      but a different extension drops the entry, as the new file may
+      now take precedence ("index.ts" created beside "index.js")
+
+  Errors are not cached.
+
+  Every invalidation starts a new generation. A result is only
+  inserted if no invalidation happened since the lookup before it was
+  resolved, otherwise it may have been computed from files that have
+  changed since and would never be invalidated.
+*/
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::public::FileEvent;
+use crate::public::Resolution;
+use crate::public::ResolveOptions;
+use crate::public::ResolveResult;
+
+use super::PipelineResult;
+
+#[derive(Debug, Default)]
+pub struct ResolveCache {
+  state: RwLock<CacheState>,
+  hits: AtomicUsize,
+  misses: AtomicUsize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheKey {
+  pub dir: PathBuf,
+  pub specifier: String,
+  pub options: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheStats {
+  pub hits: usize,
+  pub misses: usize,
+  pub entries: usize,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+  generation: usize,
+  entries: HashMap<CacheKey, PipelineResult>,
+  on_change: HashMap<PathBuf, HashSet<CacheKey>>,
+  on_create: HashMap<PathBuf, HashSet<CacheKey>>,
+  // Resolved file path without its extension
+  on_create_sibling: HashMap<PathBuf, HashSet<CacheKey>>,
+}
+
+// The reverse indexes of CacheState
+#[derive(Clone, Copy, Debug)]
+enum Index {
+  Change,
+  Create,
+  CreateSibling,
+}
+
+impl CacheKey {
+  // Resolvers are given the directory the specifier is imported from
+  // (see GraphBuilder), it is used as is so lookups never touch the disk
+  pub fn new(
+    from_dir: &Path,
+    specifier: &str,
+    options: &ResolveOptions,
+  ) -> Self {
+    Self {
+      dir: from_dir.to_path_buf(),
+      specifier: specifier.to_string(),
+      options: serde_json::to_string(options).unwrap(),
+    }
+  }
+}
+
+impl ResolveCache {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn get(
+    &self,
+    key: &CacheKey,
+  ) -> Option<PipelineResult> {
+    let entry = self.state.read().unwrap().entries.get(key).cloned();
+
+    match entry {
+      Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+      None => self.misses.fetch_add(1, Ordering::Relaxed),
+    };
+
+    return entry;
+  }
+
+  // Taken before resolving a key that missed, see insert
+  pub fn generation(&self) -> usize {
+    self.state.read().unwrap().generation
+  }
+
+  // Skipped when the cache was invalidated after the generation was
+  // taken, returns whether the entry was inserted
+  pub fn insert(
+    &self,
+    key: CacheKey,
+    entry: PipelineResult,
+    generation: usize,
+  ) -> bool {
+    let mut state = self.state.write().unwrap();
+    if state.generation != generation {
+      return false;
+    }
+    state.insert(key, entry);
+    return true;
+  }
+
+  // Drops every entry that depends on the file in the event,
+  // returns how many entries were removed
+  pub fn invalidate(
+    &self,
+    event: &FileEvent,
+  ) -> usize {
+    let mut state = self.state.write().unwrap();
+    state.generation += 1;
+
+    let keys = match event {
+      FileEvent::Change(file_path) | FileEvent::Delete(file_path) => state
+        .on_change
+        .remove(file_path)
+        .unwrap_or_default(),
+      FileEvent::Create(file_path) => {
+        let mut keys = state.on_create.remove(file_path).unwrap_or_default();
+        if let Some(siblings) = state.on_create_sibling.remove(&file_path.with_extension("")) {
+          keys.extend(siblings);
+        }
+        keys
+      }
+    };
+
+    let mut removed = 0;
+    for key in keys {
+      if state.remove(&key) {
+        removed += 1;
+      }
+    }
+    return removed;
+  }
+
+  pub fn entries(&self) -> Vec<(CacheKey, PipelineResult)> {
+    let state = self.state.read().unwrap();
+    return state
+      .entries
+      .iter()
+      .map(|(key, entry)| (key.clone(), entry.clone()))
+      .collect();
+  }
+
+  pub fn stats(&self) -> CacheStats {
+    CacheStats {
+      hits: self.hits.load(Ordering::Relaxed),
+      misses: self.misses.load(Ordering::Relaxed),
+      entries: self.state.read().unwrap().entries.len(),
+    }
+  }
+}
+
+impl CacheState {
+  fn insert(
+    &mut self,
+    key: CacheKey,
+    entry: PipelineResult,
+  ) {
+    self.remove(&key);
+    for (index, file_path) in dependencies(&entry.result) {
+      self
+        .index_mut(index)
+        .entry(file_path)
+        .or_default()
+        .insert(key.clone());
+    }
+    self.entries.insert(key, entry);
+  }
+
+  // Removes the entry along with its keys in the reverse indexes
+  fn remove(
+    &mut self,
+    key: &CacheKey,
+  ) -> bool {
+    let Some(entry) = self.entries.remove(key) else {
+      return false;
+    };
+    for (index, file_path) in dependencies(&entry.result) {
+      let index = self.index_mut(index);
+      let Some(keys) = index.get_mut(&file_path) else {
+        continue;
+      };
+      keys.remove(key);
+      if keys.is_empty() {
+        index.remove(&file_path);
+      }
+    }
+    return true;
+  }
+
+  fn index_mut(
+    &mut self,
+    index: Index,
+  ) -> &mut HashMap<PathBuf, HashSet<CacheKey>> {
+    match index {
+      Index::Change => &mut self.on_change,
+      Index::Create => &mut self.on_create,
+      Index::CreateSibling => &mut self.on_create_sibling,
+    }
+  }
+}
+
+// The files a result depends on and the index each one goes in
+fn dependencies(result: &ResolveResult) -> Vec<(Index, PathBuf)> {
+  let mut dependencies = Vec::<(Index, PathBuf)>::new();
+
+  if let Resolution::Path(file_path) = &result.resolution {
+    dependencies.push((Index::Change, file_path.clone()));
+    dependencies.push((Index::CreateSibling, file_path.with_extension("")));
+  }
+  for file_path in &result.invalidate_on_file_change {
+    dependencies.push((Index::Change, file_path.clone()));
+  }
+  for file_path in &result.invalidate_on_file_create {
+    dependencies.push((Index::Create, file_path.clone()));
+  }
+
+  return dependencies;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key(specifier: &str) -> CacheKey {
+    CacheKey::new(Path::new("/"), specifier, &ResolveOptions::default())
+  }
+
+  fn entry(result: ResolveResult) -> PipelineResult {
+    PipelineResult {
+      resolver_name: Some("test".to_string()),
+      result,
+    }
+  }
+
+  fn path_entry(file_path: &str) -> PipelineResult {
+    entry(ResolveResult::path(PathBuf::from(file_path)))
+  }
+
+  fn insert(
+    cache: &ResolveCache,
+    specifier: &str,
+    entry: PipelineResult,
+  ) {
+    assert!(cache.insert(key(specifier), entry, cache.generation()));
+  }
+
+  #[test]
+  fn changing_the_resolved_file_drops_only_its_entries() {
+    let cache = ResolveCache::new();
+    insert(&cache, "./a", path_entry("/a.js"));
+    insert(&cache, "./b", path_entry("/b.js"));
+
+    assert_eq!(
+      cache.invalidate(&FileEvent::Change(PathBuf::from("/a.js"))),
+      1
+    );
+    assert_eq!(cache.get(&key("./a")), None);
+    assert_eq!(cache.get(&key("./b")), Some(path_entry("/b.js")));
+
+    assert_eq!(
+      cache.invalidate(&FileEvent::Delete(PathBuf::from("/b.js"))),
+      1
+    );
+    assert_eq!(cache.get(&key("./b")), None);
+  }
+
+  #[test]
+  fn reported_files_drop_entries() {
+    let cache = ResolveCache::new();
+    insert(
+      &cache,
+      "pkg",
+      entry(ResolveResult {
+        invalidate_on_file_change: vec![PathBuf::from("/pkg/package.json")],
+        invalidate_on_file_create: vec![PathBuf::from("/node_modules/pkg")],
+        ..ResolveResult::path(PathBuf::from("/pkg/index.js"))
+      }),
+    );
+
+    // Creating a file that was expected to change does nothing
+    assert_eq!(
+      cache.invalidate(&FileEvent::Create(PathBuf::from("/pkg/package.json"))),
+      0
+    );
+    assert_eq!(
+      cache.invalidate(&FileEvent::Create(PathBuf::from("/node_modules/pkg"))),
+      1
+    );
+    assert_eq!(cache.get(&key("pkg")), None);
+  }
+
+  #[test]
+  fn creating_a_sibling_drops_entries() {
+    let cache = ResolveCache::new();
+    insert(&cache, "./index", path_entry("/index.js"));
+
+    assert_eq!(
+      cache.invalidate(&FileEvent::Create(PathBuf::from("/other.ts"))),
+      0
+    );
+    assert_eq!(
+      cache.invalidate(&FileEvent::Create(PathBuf::from("/index.ts"))),
+      1
+    );
+    assert_eq!(cache.get(&key("./index")), None);
+  }
+
+  #[test]
+  fn results_resolved_before_an_invalidation_are_not_inserted() {
+    let cache = ResolveCache::new();
+    let generation = cache.generation();
+
+    cache.invalidate(&FileEvent::Change(PathBuf::from("/a.js")));
+
+    assert!(!cache.insert(key("./a"), path_entry("/a.js"), generation));
+    assert_eq!(cache.get(&key("./a")), None);
+    assert!(cache.insert(key("./a"), path_entry("/a.js"), cache.generation()));
+  }
+
+  #[test]
+  fn removed_entries_leave_nothing_in_the_indexes() {
+    let cache = ResolveCache::new();
+    insert(
+      &cache,
+      "./a",
+      entry(ResolveResult {
+        invalidate_on_file_change: vec![PathBuf::from("/package.json")],
+        invalidate_on_file_create: vec![PathBuf::from("/a.ts")],
+        ..ResolveResult::path(PathBuf::from("/a.js"))
+      }),
+    );
+    // Replacing an entry drops the files the old one depended on
+    insert(&cache, "./a", path_entry("/a.mjs"));
+    insert(&cache, "./b", path_entry("/a.mjs"));
+
+    let state = cache.state.read().unwrap();
+    assert_eq!(state.on_change.len(), 1);
+    assert_eq!(state.on_change[Path::new("/a.mjs")].len(), 2);
+    assert!(state.on_create.is_empty());
+    drop(state);
+
+    cache.invalidate(&FileEvent::Change(PathBuf::from("/a.mjs")));
+
+    let state = cache.state.read().unwrap();
+    assert!(state.entries.is_empty());
+    assert!(state.on_change.is_empty());
+    assert!(state.on_create.is_empty());
+    assert!(state.on_create_sibling.is_empty());
+  }
+}
@@ -0,0 +1,53 @@
+/*
+  A fixed number of threads that run jobs in the order they were
+  queued. Used to run resolvers concurrently without starting a
+  thread for every resolver on every resolve.
+
+  A job that panics only takes itself down, the thread moves on
+  to the next job. Dropping the pool lets the threads exit once
+  the jobs already queued are done
+*/
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Debug)]
+pub struct ThreadPool {
+  tx_job: Sender<Job>,
+}
+
+impl ThreadPool {
+  pub fn new(size: usize) -> Self {
+    let (tx_job, rx_job) = channel::<Job>();
+    let rx_job = Arc::new(Mutex::new(rx_job));
+
+    for _ in 0..size.max(1) {
+      let rx_job = rx_job.clone();
+
+      thread::spawn(move || loop {
+        // The lock is released before the job runs
+        let Ok(job) = rx_job.lock().unwrap().recv() else {
+          break;
+        };
+        panic::catch_unwind(AssertUnwindSafe(job)).ok();
+      });
+    }
+
+    return Self { tx_job };
+  }
+
+  pub fn spawn<F>(
+    &self,
+    job: F,
+  ) where
+    F: FnOnce() + Send + 'static,
+  {
+    self.tx_job.send(Box::new(job)).unwrap();
+  }
+}
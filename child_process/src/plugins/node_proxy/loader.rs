@@ -0,0 +1,170 @@
+/*
+  This is a "Plugin Proxy" that runs functions in the
+  Node workers remotely via the NodeInstance, translating
+  the requests/responses to match the interface of the
+  internal "Loader" trait
+*/
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::node_adapter::NodeError;
+use crate::node_adapter::NodeInstance;
+use crate::public::LoadError;
+use crate::public::LoadOutput;
+use crate::public::Loader;
+use crate::public::ResolvedId;
+use crate::public::VirtualId;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::PluginLoadError;
+
+#[derive(Debug)]
+pub struct LoaderNodeProxy {
+  loader_key: String,
+  node_instance: Arc<NodeInstance>,
+}
+
+impl LoaderNodeProxy {
+  pub fn new(
+    node_instance: Arc<NodeInstance>,
+    specifier: &str,
+    config_path: &Path,
+  ) -> Result<Self, PluginLoadError> {
+    let req = LoadLoaderRequest {
+      specifier: specifier.to_string(),
+      config_path: config_path.to_path_buf(),
+    };
+
+    let responses = match node_instance
+      .send_all::<_, Option<LoadLoaderResponse>>("load_loader", &req)
+    {
+      Ok(responses) => responses,
+      Err(NodeError::NoResponse) => {
+        return Err(PluginLoadError::Unreachable {
+          specifier: specifier.to_string(),
+        })
+      }
+      Err(error) => {
+        return Err(PluginLoadError::Failed {
+          specifier: specifier.to_string(),
+          message: error.to_string(),
+        })
+      }
+    };
+
+    for response in responses.into_iter().flatten() {
+      if let Some(message) = response.error {
+        return Err(PluginLoadError::Failed {
+          specifier: specifier.to_string(),
+          message,
+        });
+      }
+    }
+
+    return Ok(Self {
+      loader_key: specifier.to_string(),
+      node_instance,
+    });
+  }
+}
+
+impl Loader for LoaderNodeProxy {
+  fn load(
+    &self,
+    resolved_id: &ResolvedId,
+  ) -> Result<Option<LoadOutput>, LoadError> {
+    let (file_path, virtual_id) = match resolved_id {
+      ResolvedId::Path(file_path) => (Some(file_path.clone()), None),
+      ResolvedId::Virtual(virtual_id) => (None, Some(virtual_id.clone())),
+    };
+    let req = RunLoaderRequest {
+      loader_key: self.loader_key.clone(),
+      file_path,
+      virtual_id,
+    };
+
+    // JS loaders return null to pass on to the next loader
+    let response = match self
+      .node_instance
+      .send_blocking::<_, Option<RunLoaderResponse>>("run_loader", &req)
+    {
+      Ok(response) => response,
+      Err(NodeError::NoResponse) => {
+        return Err(LoadError::Unreachable(format!(
+          "No response from loader \"{}\"",
+          self.loader_key
+        )))
+      }
+      Err(NodeError::InvalidResponse(error)) => {
+        return Err(LoadError::Failed(format!(
+          "Invalid response from loader \"{}\": {}",
+          self.loader_key, error
+        )))
+      }
+    };
+
+    let Some(response) = response else {
+      return Ok(None);
+    };
+
+    return response.into_output();
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct LoadLoaderRequest {
+  pub specifier: String,
+  pub config_path: PathBuf,
+}
+
+// Workers answer null once the loader is loaded, or the error it threw
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct LoadLoaderResponse {
+  pub error: Option<String>,
+}
+
+// Only one of file_path and virtual_id is set
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct RunLoaderRequest {
+  pub loader_key: String,
+  pub file_path: Option<PathBuf>,
+  pub virtual_id: Option<VirtualId>,
+}
+
+/*
+  The object a JS loader returns (or null), every field is optional,
+  without code the module is passed on to the next loader:
+  {
+    code?: string,
+    source_map?: string,
+    error?: string,
+  }
+*/
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct RunLoaderResponse {
+  pub code: Option<String>,
+  pub source_map: Option<String>,
+  pub error: Option<String>,
+}
+
+impl RunLoaderResponse {
+  fn into_output(self) -> Result<Option<LoadOutput>, LoadError> {
+    if let Some(error) = self.error {
+      return Err(LoadError::Failed(error));
+    }
+
+    let Some(code) = self.code else {
+      return Ok(None);
+    };
+
+    return Ok(Some(LoadOutput {
+      code,
+      source_map: self.source_map,
+    }));
+  }
+}
@@ -0,0 +1,140 @@
+/*
+  Hashes the files of a JS plugin so results produced by the plugin
+  can be reused across runs and thrown away when the plugin is edited
+  or upgraded.
+
+  Starting at the file Node.js resolved the plugin specifier to, every
+  file it imports or requires with a relative specifier is hashed (see
+  scan_imports), and so on for those files. Packages the plugin
+  imports by name are not followed, the closest package.json is
+  hashed instead so changes to the plugin's dependencies are noticed.
+*/
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::graph::scan_imports;
+
+// Tried in order for a relative specifier, like require() does
+const EXTENSIONS: [&str; 5] = ["", ".js", ".cjs", ".mjs", ".json"];
+
+pub fn hash_plugin(resolved_path: &Path) -> Option<String> {
+  let mut files = BTreeSet::<PathBuf>::new();
+  collect_files(&fs::canonicalize(resolved_path).ok()?, &mut files)?;
+
+  let package_json = resolved_path
+    .ancestors()
+    .skip(1)
+    .map(|dir| dir.join("package.json"))
+    .find(|file_path| file_path.is_file());
+  files.extend(package_json);
+
+  let mut hasher = Xxh3::new();
+  for file_path in files {
+    let contents = fs::read(&file_path).ok()?;
+    hasher.update(file_path.to_string_lossy().as_bytes());
+    hasher.update(&contents);
+  }
+
+  return Some(format!("{:016x}", hasher.digest()));
+}
+
+// Adds the file and everything it imports with a relative specifier,
+// paths are canonicalized so every file is only visited once
+fn collect_files(
+  file_path: &Path,
+  files: &mut BTreeSet<PathBuf>,
+) -> Option<()> {
+  if !files.insert(file_path.to_path_buf()) {
+    return Some(());
+  }
+
+  let code = fs::read_to_string(file_path).ok()?;
+  let dir = file_path.parent()?;
+
+  for dependency in scan_imports(&code) {
+    if !dependency.specifier.starts_with("./") && !dependency.specifier.starts_with("../") {
+      continue;
+    }
+    if let Some(dependency_path) = resolve_relative(&dir.join(&dependency.specifier)) {
+      collect_files(&dependency_path, files)?;
+    }
+  }
+
+  return Some(());
+}
+
+fn resolve_relative(path: &Path) -> Option<PathBuf> {
+  let index = path.join("index.js");
+  let candidates = EXTENSIONS
+    .iter()
+    .map(|extension| {
+      let mut candidate = path.as_os_str().to_os_string();
+      candidate.push(extension);
+      PathBuf::from(candidate)
+    })
+    .chain([index]);
+
+  for candidate in candidates {
+    if candidate.is_file() {
+      return fs::canonicalize(candidate).ok();
+    }
+  }
+  return None;
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env;
+  use std::process;
+
+  use super::*;
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("mach-plugin-hash-{}-{}", process::id(), name));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    return dir;
+  }
+
+  #[test]
+  fn hashes_only_the_files_the_plugin_imports() {
+    let dir = temp_dir("imports");
+    let entry = dir.join("index.js");
+    fs::write(
+      &entry,
+      "const util = require('./util')\nconst path = require('node:path')",
+    )
+    .unwrap();
+    fs::write(dir.join("util.js"), "module.exports = 1").unwrap();
+    fs::write(dir.join("unrelated.js"), "module.exports = 1").unwrap();
+    let hash = hash_plugin(&entry).unwrap();
+
+    fs::write(dir.join("unrelated.js"), "module.exports = 2").unwrap();
+    assert_eq!(hash_plugin(&entry), Some(hash.clone()));
+
+    fs::write(dir.join("util.js"), "module.exports = 2").unwrap();
+    assert_ne!(hash_plugin(&entry), Some(hash));
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn visits_import_cycles_once() {
+    let dir = temp_dir("cycles");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("a.js"), "import './lib'").unwrap();
+    fs::write(dir.join("lib").join("index.js"), "import '../a.js'").unwrap();
+    assert!(hash_plugin(&dir.join("a.js")).is_some());
+
+    // Missing files are skipped, the plugin fails on its own when loaded
+    fs::write(dir.join("a.js"), "import './missing'").unwrap();
+    assert!(hash_plugin(&dir.join("a.js")).is_some());
+    assert_eq!(hash_plugin(&dir.join("missing.js")), None);
+
+    fs::remove_dir_all(&dir).ok();
+  }
+}
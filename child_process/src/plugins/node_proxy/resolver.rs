@@ -3,20 +3,37 @@
   Node workers remotely via the NodeInstance, translating
   the requests/responses to match the interface of the 
   internal "Resolver" trait
+
+  Each proxy loads its own instance of the plugin with its own
+  options, the workers know it by the id the NodeInstance gave it
 */
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use crate::node_adapter::NodeError;
 use crate::node_adapter::NodeInstance;
+use crate::public::FileEvent;
+use crate::public::ModuleType;
+use crate::public::Resolution;
+use crate::public::ResolveError;
+use crate::public::ResolveOptions;
+use crate::public::ResolveResult;
 use crate::public::Resolver;
+use crate::public::VirtualId;
 
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::plugin_hash::hash_plugin;
+use super::PluginExports;
+use super::PluginLoadError;
+
 #[derive(Debug)]
 pub struct ResolverNodeProxy {
-  resolver_key: String,
+  specifier: String,
+  resolver_id: usize,
+  identity: Option<String>,
   node_instance: Arc<NodeInstance>,
 }
 
@@ -24,19 +41,71 @@ impl ResolverNodeProxy {
   pub fn new(
     node_instance: Arc<NodeInstance>,
     specifier: &str,
-  ) -> Self {
+    config_path: &Path,
+    options: &serde_json::Value,
+  ) -> Result<Self, PluginLoadError> {
+    let resolver_id = node_instance.new_plugin_id();
     let req = LoadResolverRequest {
+      resolver_id,
       specifier: specifier.to_string(),
+      config_path: config_path.to_path_buf(),
+      options: options.clone(),
     };
 
-    node_instance
-      .send_all("load_resolver",&req)
-      .unwrap();
+    let responses = match node_instance
+      .send_all::<_, LoadResolverResponse>("load_resolver", &req)
+    {
+      Ok(responses) => responses,
+      Err(NodeError::NoResponse) => {
+        return Err(PluginLoadError::Unreachable {
+          specifier: specifier.to_string(),
+        })
+      }
+      Err(error) => {
+        return Err(PluginLoadError::Failed {
+          specifier: specifier.to_string(),
+          message: error.to_string(),
+        })
+      }
+    };
 
-    Self {
-      resolver_key: specifier.to_string(),
-      node_instance,
+    // Every worker loads the same module, any of them can describe it
+    let mut resolved = None;
+    for response in responses {
+      if let Some(message) = response.error {
+        return Err(PluginLoadError::Failed {
+          specifier: specifier.to_string(),
+          message,
+        });
+      }
+      if let (Some(resolved_path), Some(exports)) = (response.resolved_path, response.exports) {
+        resolved = Some((resolved_path, exports));
+      }
+    }
+    let Some((resolved_path, exports)) = resolved else {
+      return Err(PluginLoadError::Unreachable {
+        specifier: specifier.to_string(),
+      });
+    };
+
+    if let Err(problem) = exports.check("resolve") {
+      return Err(PluginLoadError::InvalidExports {
+        specifier: specifier.to_string(),
+        problem,
+      });
     }
+
+    // The plugin is identified by its specifier, its options and the
+    // contents of its files so cached results are dropped when it changes
+    let identity = hash_plugin(&resolved_path)
+      .map(|hash| format!("{}#{}#{}", specifier, options, hash));
+
+    return Ok(Self {
+      specifier: specifier.to_string(),
+      resolver_id,
+      identity,
+      node_instance,
+    });
   }
 }
 
@@ -45,35 +114,148 @@ impl Resolver for ResolverNodeProxy {
     &self,
     from_path: &Path,
     specifier: &str,
-  ) -> Option<PathBuf> {
+    options: &ResolveOptions,
+  ) -> Result<ResolveResult, ResolveError> {
     let req = RunResolverRequest {
-      resolver_key: self.resolver_key.clone(),
+      resolver_id: self.resolver_id,
       from_path: from_path.to_path_buf(),
       specifier: specifier.to_string(),
+      options: options.clone(),
     };
 
-    let response: RunResolverResponse = self
+    // JS resolvers return null to pass on to the next resolver
+    let response = match self
       .node_instance
-      .send_blocking("run_resolver",&req)
-      .unwrap();
+      .send_blocking::<_, Option<RunResolverResponse>>("run_resolver", &req)
+    {
+      Ok(response) => response,
+      Err(NodeError::NoResponse) => {
+        return Err(ResolveError::Unreachable(format!(
+          "No response from resolver \"{}\"",
+          self.specifier
+        )))
+      }
+      Err(NodeError::InvalidResponse(error)) => {
+        return Err(ResolveError::Failed(format!(
+          "Invalid response from resolver \"{}\": {}",
+          self.specifier, error
+        )))
+      }
+    };
 
-    return Some(response.file_path);
+    let Some(response) = response else {
+      return Ok(ResolveResult::default());
+    };
+
+    return response.into_result();
+  }
+
+  fn identity(&self) -> Option<String> {
+    self.identity.clone()
+  }
+
+  fn invalidate(
+    &self,
+    events: &[FileEvent],
+  ) {
+    let req = InvalidateRequest {
+      resolver_id: self.resolver_id,
+      events: events.to_vec(),
+    };
+
+    // Every worker holds its own instance of the plugin, workers handle
+    // messages in order so this lands before any later resolve. Without
+    // workers there is no instance to invalidate
+    self.node_instance.notify_all("invalidate", &req).ok();
   }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct LoadResolverRequest {
+  pub resolver_id: usize,
   pub specifier: String,
+  pub config_path: PathBuf,
+  pub options: serde_json::Value,
+}
+
+// Either the error or the rest is set
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct LoadResolverResponse {
+  pub resolved_path: Option<PathBuf>,
+  pub exports: Option<PluginExports>,
+  pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct InvalidateRequest {
+  pub resolver_id: usize,
+  pub events: Vec<FileEvent>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct RunResolverRequest {
-  pub resolver_key: String,
+  pub resolver_id: usize,
   pub from_path: PathBuf,
   pub specifier: String,
+  pub options: ResolveOptions,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/*
+  The object a JS resolver returns (or null), every field is optional:
+  {
+    file_path?: string,
+    virtual_id?: { namespace: string, id: string },
+    external?: boolean,
+    builtin?: string,
+    side_effects?: boolean,
+    module_type?: "esm" | "cjs",
+    query?: string,
+    invalidate_on_file_change?: string[],
+    invalidate_on_file_create?: string[],
+    error?: string,
+  }
+*/
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
 struct RunResolverResponse {
-  pub file_path: PathBuf,
+  pub file_path: Option<PathBuf>,
+  pub virtual_id: Option<VirtualId>,
+  pub external: bool,
+  pub builtin: Option<String>,
+  pub side_effects: Option<bool>,
+  pub module_type: Option<ModuleType>,
+  pub query: Option<String>,
+  pub invalidate_on_file_change: Vec<PathBuf>,
+  pub invalidate_on_file_create: Vec<PathBuf>,
+  pub error: Option<String>,
+}
+
+impl RunResolverResponse {
+  fn into_result(self) -> Result<ResolveResult, ResolveError> {
+    if let Some(error) = self.error {
+      return Err(ResolveError::Failed(error));
+    }
+
+    let resolution = if let Some(builtin) = self.builtin {
+      Resolution::Builtin(builtin)
+    } else if self.external {
+      Resolution::External
+    } else if let Some(virtual_id) = self.virtual_id {
+      Resolution::Virtual(virtual_id)
+    } else if let Some(file_path) = self.file_path {
+      Resolution::Path(file_path)
+    } else {
+      Resolution::Unresolved
+    };
+
+    return Ok(ResolveResult {
+      resolution,
+      side_effects: self.side_effects.unwrap_or(true),
+      module_type: self.module_type,
+      query: self.query,
+      invalidate_on_file_change: self.invalidate_on_file_change,
+      invalidate_on_file_create: self.invalidate_on_file_create,
+    });
+  }
 }
@@ -0,0 +1,177 @@
+/*
+  This is a "Plugin Proxy" that runs functions in the
+  Node workers remotely via the NodeInstance, translating
+  the requests/responses to match the interface of the
+  internal "Transformer" trait
+*/
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::node_adapter::NodeError;
+use crate::node_adapter::NodeInstance;
+use crate::public::Dependency;
+use crate::public::TransformError;
+use crate::public::TransformInput;
+use crate::public::TransformOutput;
+use crate::public::Transformer;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::PluginLoadError;
+
+#[derive(Debug)]
+pub struct TransformerNodeProxy {
+  transformer_key: String,
+  node_instance: Arc<NodeInstance>,
+}
+
+impl TransformerNodeProxy {
+  pub fn new(
+    node_instance: Arc<NodeInstance>,
+    specifier: &str,
+    config_path: &Path,
+  ) -> Result<Self, PluginLoadError> {
+    let req = LoadTransformerRequest {
+      specifier: specifier.to_string(),
+      config_path: config_path.to_path_buf(),
+    };
+
+    let responses = match node_instance
+      .send_all::<_, Option<LoadTransformerResponse>>("load_transformer", &req)
+    {
+      Ok(responses) => responses,
+      Err(NodeError::NoResponse) => {
+        return Err(PluginLoadError::Unreachable {
+          specifier: specifier.to_string(),
+        })
+      }
+      Err(error) => {
+        return Err(PluginLoadError::Failed {
+          specifier: specifier.to_string(),
+          message: error.to_string(),
+        })
+      }
+    };
+
+    for response in responses.into_iter().flatten() {
+      if let Some(message) = response.error {
+        return Err(PluginLoadError::Failed {
+          specifier: specifier.to_string(),
+          message,
+        });
+      }
+    }
+
+    return Ok(Self {
+      transformer_key: specifier.to_string(),
+      node_instance,
+    });
+  }
+}
+
+impl Transformer for TransformerNodeProxy {
+  fn transform(
+    &self,
+    input: &TransformInput,
+  ) -> Result<TransformOutput, TransformError> {
+    let req = RunTransformerRequest {
+      transformer_key: self.transformer_key.clone(),
+      file_path: input.file_path.clone(),
+      code: input.code.clone(),
+      source_map: input.source_map.clone(),
+      options: input.options.clone(),
+    };
+
+    // JS transformers return null to leave the code as it is
+    let response = match self
+      .node_instance
+      .send_blocking::<_, Option<RunTransformerResponse>>("run_transformer", &req)
+    {
+      Ok(response) => response,
+      Err(NodeError::NoResponse) => {
+        return Err(TransformError::Unreachable(format!(
+          "No response from transformer \"{}\"",
+          self.transformer_key
+        )))
+      }
+      Err(NodeError::InvalidResponse(error)) => {
+        return Err(TransformError::Failed(format!(
+          "Invalid response from transformer \"{}\": {}",
+          self.transformer_key, error
+        )))
+      }
+    };
+
+    let Some(response) = response else {
+      return Ok(TransformOutput::unchanged(input));
+    };
+
+    return response.into_output(input);
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct LoadTransformerRequest {
+  pub specifier: String,
+  pub config_path: PathBuf,
+}
+
+// Workers answer null once the transformer is loaded, or the error it threw
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct LoadTransformerResponse {
+  pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct RunTransformerRequest {
+  pub transformer_key: String,
+  pub file_path: PathBuf,
+  pub code: String,
+  pub source_map: Option<String>,
+  pub options: BTreeMap<String, serde_json::Value>,
+}
+
+/*
+  The object a JS transformer returns (or null), every field is optional:
+  {
+    code?: string,
+    source_map?: string,
+    dependencies?: { specifier: string, kind?: "import" | "require" | ... }[],
+    error?: string,
+  }
+*/
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct RunTransformerResponse {
+  pub code: Option<String>,
+  pub source_map: Option<String>,
+  pub dependencies: Vec<Dependency>,
+  pub error: Option<String>,
+}
+
+impl RunTransformerResponse {
+  fn into_output(
+    self,
+    input: &TransformInput,
+  ) -> Result<TransformOutput, TransformError> {
+    if let Some(error) = self.error {
+      return Err(TransformError::Failed(error));
+    }
+
+    // The input's map only carries over with its code
+    let (code, source_map) = match self.code {
+      Some(code) => (code, self.source_map),
+      None => (input.code.clone(), input.source_map.clone()),
+    };
+
+    return Ok(TransformOutput {
+      code,
+      source_map,
+      dependencies: self.dependencies,
+    });
+  }
+}
@@ -0,0 +1,27 @@
+/*
+  A change to a file on disk that may invalidate earlier results
+  reported by plugins. A rename is reported as a Delete of the old
+  path followed by a Create of the new one
+*/
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+// Sent to JS plugins as { "type": "create", "path": "/a/b.js" }
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "path", rename_all = "lowercase")]
+pub enum FileEvent {
+  Create(PathBuf),
+  Change(PathBuf),
+  Delete(PathBuf),
+}
+
+impl FileEvent {
+  pub fn path(&self) -> &Path {
+    match self {
+      FileEvent::Create(path) | FileEvent::Change(path) | FileEvent::Delete(path) => path,
+    }
+  }
+}
@@ -1,3 +1,11 @@
+mod file_event;
+mod loader;
+mod reporter;
 mod resolver;
+mod transformer;
 
+pub use crate::public::file_event::*;
+pub use crate::public::loader::*;
+pub use crate::public::reporter::*;
 pub use crate::public::resolver::*;
+pub use crate::public::transformer::*;
@@ -0,0 +1,380 @@
+/*
+  The mach.config.json file, found by walking up from the working
+  directory. It lists the plugins to load in order (resolvers are
+  tried in the order they are listed) and how many Node workers to
+  run them on:
+  {
+    "backend": "child_process" | "child_process_async" | "napi",
+    "workers": 4,
+    "resolvers": [
+      { "plugin": "mach:default" },
+      { "plugin": "mach:workspace", "options": { "prefer_source": true } },
+      { "plugin": "./plugin", "options": { "namespace": "virtual" } },
+      { "plugin": "./plugin" }
+    ],
+    "transformers": [{ "plugin": "./plugin/transformer" }],
+    "loaders": [{ "plugin": "./plugin/loader" }],
+    "reporters": [{ "plugin": "./plugin/reporter" }]
+  }
+
+  Plugins starting with "./" or "../" are relative to the config file,
+  other names are packages Node.js finds from the config file, as if
+  it had required them. Plugins named "mach:" are compiled into the
+  bundler. Every field is optional, without a config
+  file only the builtin resolvers are used
+
+  A resolver may be listed more than once, each entry is a separate
+  instance of the plugin created with that entry's options. Only
+  resolvers take options
+*/
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+pub const CONFIG_FILE: &str = "mach.config.json";
+
+// Prefix of the plugins that are compiled into the bundler
+pub const BUILTIN_PREFIX: &str = "mach:";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MachConfig {
+  // None when there is no config file
+  #[serde(skip)]
+  pub path: Option<PathBuf>,
+  pub backend: Option<Backend>,
+  pub workers: Option<usize>,
+  pub resolvers: Vec<PluginConfig>,
+  pub transformers: Vec<PluginConfig>,
+  pub loaders: Vec<PluginConfig>,
+  pub reporters: Vec<PluginConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginConfig {
+  // Relative plugins are made absolute when the config is loaded
+  pub plugin: String,
+  #[serde(default)]
+  pub options: serde_json::Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+  ChildProcess,
+  ChildProcessAsync,
+  Napi,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+  // The config file could not be read
+  Read {
+    path: PathBuf,
+    error: std::io::Error,
+  },
+  // The config file is not JSON or has fields of the wrong type
+  Parse {
+    path: PathBuf,
+    line: usize,
+    column: usize,
+    message: String,
+  },
+  // The config is well formed but asks for something that can't be done
+  Invalid {
+    path: PathBuf,
+    message: String,
+  },
+}
+
+impl MachConfig {
+  // Uses the closest config file in dir or its ancestors
+  pub fn find(dir: &Path) -> Result<Self, ConfigError> {
+    for dir in dir.ancestors() {
+      let path = dir.join(CONFIG_FILE);
+      if path.is_file() {
+        return Self::load(&path);
+      }
+    }
+
+    return Ok(Self {
+      resolvers: vec![
+        PluginConfig::builtin("default"),
+        PluginConfig::builtin("workspace"),
+      ],
+      ..Default::default()
+    });
+  }
+
+  pub fn load(path: &Path) -> Result<Self, ConfigError> {
+    let json = fs::read_to_string(path).map_err(|error| ConfigError::Read {
+      path: path.to_path_buf(),
+      error,
+    })?;
+
+    let mut config: Self = serde_json::from_str(&json).map_err(|error| {
+      // The position is reported separately
+      let message = error.to_string();
+      let position = format!(" at line {} column {}", error.line(), error.column());
+      ConfigError::Parse {
+        path: path.to_path_buf(),
+        line: error.line(),
+        column: error.column(),
+        message: message.strip_suffix(&position).unwrap_or(&message).to_string(),
+      }
+    })?;
+    config.path = Some(path.to_path_buf());
+
+    let config_dir = path.parent().unwrap_or(Path::new("/"));
+    for (field, plugins) in config.plugin_lists_mut() {
+      for (index, plugin) in plugins.iter_mut().enumerate() {
+        if plugin.plugin.is_empty() {
+          return Err(ConfigError::invalid(path, format!("{}[{}].plugin is empty", field, index)));
+        }
+        plugin.plugin = resolve_plugin(config_dir, &plugin.plugin);
+      }
+    }
+
+    if config.workers == Some(0) {
+      return Err(ConfigError::invalid(path, "workers must be at least 1"));
+    }
+
+    return Ok(config);
+  }
+
+  // Fails when the config asks for a backend other than the one running
+  pub fn check_backend(
+    &self,
+    running: Backend,
+  ) -> Result<(), ConfigError> {
+    match self.backend {
+      Some(backend) if backend != running => Err(self.invalid(format!(
+        "backend is \"{}\" but this is the \"{}\" backend",
+        backend, running
+      ))),
+      _ => Ok(()),
+    }
+  }
+
+  pub fn invalid(
+    &self,
+    message: impl Display,
+  ) -> ConfigError {
+    return ConfigError::invalid(&self.config_path(), message);
+  }
+
+  // The config file, or where it would be when there is none. Node.js
+  // finds the plugins named after packages from here
+  pub fn config_path(&self) -> PathBuf {
+    return self.path.clone().unwrap_or_else(|| PathBuf::from(CONFIG_FILE));
+  }
+
+  fn plugin_lists_mut(&mut self) -> [(&'static str, &mut Vec<PluginConfig>); 4] {
+    [
+      ("resolvers", &mut self.resolvers),
+      ("transformers", &mut self.transformers),
+      ("loaders", &mut self.loaders),
+      ("reporters", &mut self.reporters),
+    ]
+  }
+}
+
+impl PluginConfig {
+  fn builtin(name: &str) -> Self {
+    Self {
+      plugin: format!("{}{}", BUILTIN_PREFIX, name),
+      options: serde_json::Value::Null,
+    }
+  }
+
+  // The name of a plugin compiled into the bundler, e.g. "workspace"
+  pub fn builtin_name(&self) -> Option<&str> {
+    self.plugin.strip_prefix(BUILTIN_PREFIX)
+  }
+}
+
+impl ConfigError {
+  fn invalid(
+    path: &Path,
+    message: impl Display,
+  ) -> Self {
+    Self::Invalid {
+      path: path.to_path_buf(),
+      message: message.to_string(),
+    }
+  }
+}
+
+// Relative plugins are relative to the config file, package names are
+// left for Node.js to find from the config file
+fn resolve_plugin(
+  config_dir: &Path,
+  plugin: &str,
+) -> String {
+  if !plugin.starts_with("./") && !plugin.starts_with("../") {
+    return plugin.to_string();
+  }
+  let path = config_dir.join(plugin).components().collect::<PathBuf>();
+  return path.to_string_lossy().to_string();
+}
+
+impl Display for Backend {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      Backend::ChildProcess => write!(f, "child_process"),
+      Backend::ChildProcessAsync => write!(f, "child_process_async"),
+      Backend::Napi => write!(f, "napi"),
+    }
+  }
+}
+
+impl Display for ConfigError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      ConfigError::Read { path, error } => {
+        write!(f, "Unable to read {}: {}", path.display(), error)
+      }
+      ConfigError::Parse {
+        path,
+        line,
+        column,
+        message,
+      } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+      ConfigError::Invalid { path, message } => write!(f, "{}: {}", path.display(), message),
+    }
+  }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+  use std::env;
+  use std::process;
+
+  use super::*;
+
+  fn write_config(
+    name: &str,
+    json: &str,
+  ) -> PathBuf {
+    let dir = env::temp_dir().join(format!("mach-config-{}-{}", process::id(), name));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(CONFIG_FILE);
+    fs::write(&path, json).unwrap();
+    return path;
+  }
+
+  fn message(error: ConfigError) -> String {
+    return match error {
+      ConfigError::Invalid { message, .. } => message,
+      error => panic!("expected an invalid config, got {:?}", error),
+    };
+  }
+
+  #[test]
+  fn relative_plugins_are_relative_to_the_config_file() {
+    let path = write_config(
+      "relative",
+      r#"{
+        "resolvers": [{ "plugin": "./plugin" }, { "plugin": "../shared/plugin" }],
+        "transformers": [{ "plugin": "some-package" }],
+        "loaders": [{ "plugin": "mach:default" }]
+      }"#,
+    );
+    let dir = path.parent().unwrap();
+
+    let config = MachConfig::load(&path).unwrap();
+    assert_eq!(config.path, Some(path.clone()));
+    assert_eq!(config.config_path(), path);
+    assert_eq!(
+      PathBuf::from(&config.resolvers[0].plugin),
+      dir.join("plugin")
+    );
+    assert_eq!(
+      PathBuf::from(&config.resolvers[1].plugin),
+      dir.join("../shared/plugin")
+    );
+    assert_eq!(config.transformers[0].plugin, "some-package");
+    assert_eq!(config.loaders[0].builtin_name(), Some("default"));
+
+    fs::remove_dir_all(dir).ok();
+  }
+
+  #[test]
+  fn parse_errors_point_at_the_problem() {
+    let path = write_config("parse", "{\n  \"workers\": \"four\"\n}");
+    let Err(ConfigError::Parse {
+      line,
+      column,
+      message,
+      ..
+    }) = MachConfig::load(&path)
+    else {
+      panic!("expected a parse error");
+    };
+    assert_eq!((line, column), (2, 19));
+    assert!(!message.contains("at line"), "{}", message);
+
+    let path = write_config("unknown", r#"{ "plugins": [] }"#);
+    let error = MachConfig::load(&path).unwrap_err();
+    assert!(
+      error.to_string().contains("unknown field `plugins`"),
+      "{}",
+      error
+    );
+
+    fs::remove_dir_all(path.parent().unwrap()).ok();
+  }
+
+  #[test]
+  fn rejects_configs_that_cannot_be_used() {
+    let path = write_config("empty", r#"{ "reporters": [{ "plugin": "" }] }"#);
+    let error = MachConfig::load(&path).unwrap_err();
+    assert_eq!(message(error), "reporters[0].plugin is empty");
+
+    let path = write_config("workers", r#"{ "workers": 0 }"#);
+    let error = MachConfig::load(&path).unwrap_err();
+    assert_eq!(message(error), "workers must be at least 1");
+
+    let path = write_config("backend", r#"{ "backend": "napi" }"#);
+    let config = MachConfig::load(&path).unwrap();
+    assert!(config.check_backend(Backend::Napi).is_ok());
+    let error = config.check_backend(Backend::ChildProcess).unwrap_err();
+    assert_eq!(
+      message(error),
+      "backend is \"napi\" but this is the \"child_process\" backend"
+    );
+
+    fs::remove_dir_all(path.parent().unwrap()).ok();
+  }
+
+  #[test]
+  fn missing_config_files() {
+    let path = write_config("missing", "{}");
+    fs::remove_file(&path).unwrap();
+    let Err(ConfigError::Read {
+      path: read_path, ..
+    }) = MachConfig::load(&path)
+    else {
+      panic!("expected a read error");
+    };
+    assert_eq!(read_path, path);
+
+    let config = MachConfig::default();
+    assert_eq!(config.config_path(), PathBuf::from(CONFIG_FILE));
+
+    fs::remove_dir_all(path.parent().unwrap()).ok();
+  }
+}
@@ -0,0 +1,5 @@
+mod mach_config;
+mod plugin_set;
+
+pub use crate::config::mach_config::*;
+pub use crate::config::plugin_set::*;
@@ -0,0 +1,273 @@
+/*
+  The plugins listed in the config, loaded and ready to use. The set
+  acts as a single transformer, loader and reporter:
+    transformers run one after the other, each on the output of the last
+    loaders are asked in order until one claims the module
+    reporters all receive every event
+*/
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::node_adapter::NodeInstance;
+use crate::pipeline::ResolverPipeline;
+use crate::plugins::DefaultResolver;
+use crate::plugins::LoaderNodeProxy;
+use crate::plugins::ReporterNodeProxy;
+use crate::plugins::ResolverNodeProxy;
+use crate::plugins::TransformerNodeProxy;
+use crate::plugins::WorkspaceResolver;
+use crate::public::LoadError;
+use crate::public::LoadOutput;
+use crate::public::Loader;
+use crate::public::Reporter;
+use crate::public::ReporterEvent;
+use crate::public::ResolvedId;
+use crate::public::TransformError;
+use crate::public::TransformInput;
+use crate::public::TransformOutput;
+use crate::public::Transformer;
+
+use super::ConfigError;
+use super::MachConfig;
+use super::PluginConfig;
+
+#[derive(Debug)]
+pub struct PluginSet {
+  pub resolvers: ResolverPipeline,
+  transformers: Vec<Box<dyn Transformer>>,
+  loaders: Vec<Box<dyn Loader>>,
+  reporters: Vec<Box<dyn Reporter>>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WorkspaceOptions {
+  prefer_source: bool,
+}
+
+impl Default for WorkspaceOptions {
+  fn default() -> Self {
+    Self {
+      prefer_source: true,
+    }
+  }
+}
+
+impl PluginSet {
+  pub async fn load(
+    config: &MachConfig,
+    node_instance: &Arc<NodeInstance>,
+    concurrent: bool,
+  ) -> Result<Self, ConfigError> {
+    let config_path = config.config_path();
+    let mut resolvers = ResolverPipeline::new(concurrent);
+    for (index, plugin) in config.resolvers.iter().enumerate() {
+      // The same plugin can be listed more than once with different
+      // options, the name tells the instances apart in the cache
+      let name = format!("resolvers[{}]:{}", index, plugin.plugin);
+      let priority = index as i32;
+
+      match plugin.builtin_name() {
+        Some("default") => resolvers.add(&name, priority, DefaultResolver::new()),
+        Some("workspace") => {
+          let options: WorkspaceOptions = builtin_options(config, "resolvers", index, plugin)?;
+          resolvers.add(&name, priority, WorkspaceResolver::new(options.prefer_source));
+        }
+        Some(_) => {
+          return Err(config.invalid(format!(
+            "resolvers[{}]: there is no builtin resolver \"{}\", expected \"mach:default\" or \"mach:workspace\"",
+            index, plugin.plugin
+          )))
+        }
+        None => {
+          let resolver = ResolverNodeProxy::new(node_instance.clone(), &plugin.plugin, &config_path, &plugin.options)
+            .await
+            .map_err(|error| config.invalid(format!("resolvers[{}]: {}", index, error)))?;
+          resolvers.add(&name, priority, resolver);
+        }
+      }
+    }
+
+    let mut transformers = Vec::<Box<dyn Transformer>>::new();
+    let plugins = node_plugins(config, "transformers", &config.transformers)?;
+    for (index, plugin) in plugins.into_iter().enumerate() {
+      let transformer = TransformerNodeProxy::new(node_instance.clone(), plugin, &config_path)
+        .await
+        .map_err(|error| config.invalid(format!("transformers[{}]: {}", index, error)))?;
+      transformers.push(Box::new(transformer));
+    }
+
+    let mut loaders = Vec::<Box<dyn Loader>>::new();
+    let plugins = node_plugins(config, "loaders", &config.loaders)?;
+    for (index, plugin) in plugins.into_iter().enumerate() {
+      let loader = LoaderNodeProxy::new(node_instance.clone(), plugin, &config_path)
+        .await
+        .map_err(|error| config.invalid(format!("loaders[{}]: {}", index, error)))?;
+      loaders.push(Box::new(loader));
+    }
+
+    let mut reporters = Vec::<Box<dyn Reporter>>::new();
+    let plugins = node_plugins(config, "reporters", &config.reporters)?;
+    for (index, plugin) in plugins.into_iter().enumerate() {
+      let reporter = ReporterNodeProxy::new(node_instance.clone(), plugin, &config_path)
+        .await
+        .map_err(|error| config.invalid(format!("reporters[{}]: {}", index, error)))?;
+      reporters.push(Box::new(reporter));
+    }
+
+    return Ok(Self {
+      resolvers,
+      transformers,
+      loaders,
+      reporters,
+    });
+  }
+}
+
+#[async_trait]
+impl Transformer for PluginSet {
+  async fn transform(
+    &self,
+    input: &TransformInput,
+  ) -> Result<TransformOutput, TransformError> {
+    let mut output = TransformOutput::unchanged(input);
+
+    for transformer in &self.transformers {
+      let next = transformer.transform(&TransformInput {
+        code: output.code,
+        source_map: output.source_map,
+        ..input.clone()
+      })
+      .await?;
+      output.code = next.code;
+      output.source_map = next.source_map;
+      output.dependencies.extend(next.dependencies);
+    }
+
+    return Ok(output);
+  }
+}
+
+#[async_trait]
+impl Loader for PluginSet {
+  async fn load(
+    &self,
+    resolved_id: &ResolvedId,
+  ) -> Result<Option<LoadOutput>, LoadError> {
+    for loader in &self.loaders {
+      if let Some(output) = loader.load(resolved_id).await? {
+        return Ok(Some(output));
+      }
+    }
+    return Ok(None);
+  }
+}
+
+impl Reporter for PluginSet {
+  fn report(
+    &self,
+    event: &ReporterEvent,
+  ) {
+    for reporter in &self.reporters {
+      reporter.report(event);
+    }
+  }
+}
+
+// Only resolvers have builtins, everything else runs in Node.js. Only
+// resolvers take options too, through their factory
+fn node_plugins<'a>(
+  config: &MachConfig,
+  field: &str,
+  plugins: &'a [PluginConfig],
+) -> Result<Vec<&'a str>, ConfigError> {
+  let mut names = vec![];
+  for (index, plugin) in plugins.iter().enumerate() {
+    if plugin.builtin_name().is_some() {
+      return Err(config.invalid(format!(
+        "{}[{}]: there are no builtin {}, \"{}\" can't be used",
+        field, index, field, plugin.plugin
+      )));
+    }
+    if !plugin.options.is_null() {
+      return Err(config.invalid(format!(
+        "{}[{}]: \"{}\" can't be given options, only resolvers take options",
+        field, index, plugin.plugin
+      )));
+    }
+    names.push(plugin.plugin.as_str());
+  }
+  return Ok(names);
+}
+
+fn builtin_options<T: Default + for<'de> Deserialize<'de>>(
+  config: &MachConfig,
+  field: &str,
+  index: usize,
+  plugin: &PluginConfig,
+) -> Result<T, ConfigError> {
+  if plugin.options.is_null() {
+    return Ok(T::default());
+  }
+  return serde_json::from_value(plugin.options.clone()).map_err(|error| {
+    config.invalid(format!(
+      "{}[{}]: invalid options for \"{}\": {}",
+      field, index, plugin.plugin, error
+    ))
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn plugin(
+    name: &str,
+    options: serde_json::Value,
+  ) -> PluginConfig {
+    return PluginConfig {
+      plugin: name.to_string(),
+      options,
+    };
+  }
+
+  fn error(plugins: &[PluginConfig]) -> String {
+    let config = MachConfig::default();
+    return node_plugins(&config, "transformers", plugins)
+      .unwrap_err()
+      .to_string();
+  }
+
+  #[test]
+  fn node_plugins_are_listed_in_order() {
+    let config = MachConfig::default();
+    let plugins = [
+      plugin("/a", serde_json::Value::Null),
+      plugin("b", serde_json::Value::Null),
+    ];
+    assert_eq!(
+      node_plugins(&config, "transformers", &plugins).unwrap(),
+      vec!["/a", "b"]
+    );
+  }
+
+  #[test]
+  fn node_plugins_reject_builtins_and_options() {
+    let builtin = [plugin("mach:default", serde_json::Value::Null)];
+    assert_eq!(
+      error(&builtin),
+      "mach.config.json: transformers[0]: there are no builtin transformers, \"mach:default\" can't be used"
+    );
+
+    let options = [
+      plugin("/a", serde_json::Value::Null),
+      plugin("/b", serde_json::json!({ "minify": true })),
+    ];
+    assert_eq!(
+      error(&options),
+      "mach.config.json: transformers[1]: \"/b\" can't be given options, only resolvers take options"
+    );
+  }
+}
@@ -1,37 +1,174 @@
+mod config;
+mod graph;
 mod node_adapter;
+mod pipeline;
 mod plugins;
 mod public;
+mod watcher;
 
 use std::env;
+use std::path::Path;
+use std::process;
 use std::sync::Arc;
 
+use config::Backend;
+use config::MachConfig;
+use config::PluginSet;
+use graph::GraphBuilder;
+use node_adapter::LifecycleHook;
 use node_adapter::NodeInstance;
-use plugins::DefaultResolver;
-use public::Resolver;
+use pipeline::DiskCache;
+use public::ResolveOptions;
+use watcher::FileWatcher;
 
-use crate::plugins::ResolverNodeProxy;
+use crate::public::Loader;
+use crate::public::Reporter;
+use crate::public::ReporterEvent;
+use crate::public::TransformInput;
+use crate::public::Transformer;
 
 async fn main_async() {
   // Parse CLI args
   let args: Vec<String> = env::args().collect();
-  let node_worker_count = args.get(1).unwrap_or(&"4".to_string()).parse::<usize>().unwrap();
+  let concurrent = args.iter().any(|arg| arg == "--concurrent");
+  let watch = args.iter().any(|arg| arg == "--watch");
+
+  // Plugins and the worker count come from the closest mach.config.json
+  let config = MachConfig::find(&env::current_dir().unwrap())
+    .and_then(|config| config.check_backend(Backend::ChildProcessAsync).map(|_| config));
+  let config = match config {
+    Ok(config) => config,
+    Err(error) => {
+      eprintln!("{}", error);
+      process::exit(1);
+    }
+  };
+  let node_worker_count = match args.get(1) {
+    Some(arg) => arg.parse::<usize>().unwrap(),
+    None => config.workers.unwrap_or(4),
+  };
 
   // Create a Node.js child process, spawn worker threads within it and connect to them
   let node_instance = Arc::new(NodeInstance::new(node_worker_count).await);
 
-  // Mimic loading plugins in from config
-  let mut resolvers = Vec::<Box<dyn Resolver>>::new();
-  resolvers.push(Box::new(DefaultResolver::new()));
-  resolvers.push(Box::new(ResolverNodeProxy::new(node_instance.clone(), "../plugin").await));
+  let plugins = match PluginSet::load(&config, &node_instance, concurrent).await {
+    Ok(plugins) => plugins,
+    Err(error) => {
+      eprintln!("{}", error);
+      process::exit(1);
+    }
+  };
+  let resolvers = &plugins.resolvers;
+
+  // Reuse resolutions from previous runs
+  let cache_dir = env::current_dir().unwrap().join(".mach").join("cache");
+  let disk_cache = DiskCache::new(&cache_dir);
+  disk_cache.load(resolvers);
 
   // Mimic running resolvers
   let from_path = env::current_dir().unwrap();
+  let options = ResolveOptions::default();
+
+  // Mimic a build of the example project
+  let entries = vec![from_path.parent().unwrap().join("example").join("main.js")];
+  let graph_builder = GraphBuilder::new(resolvers, options.clone());
+
+  let run = || async {
+    run_hook(&node_instance, &plugins, LifecycleHook::BuildStart).await;
+    plugins.report(&ReporterEvent::BuildStart);
+
+    let graph = graph_builder.build(&entries).await;
+    println!("graph: {}", serde_json::to_string_pretty(&graph).unwrap());
 
-  for resolver in &resolvers {
-    let Some(file_path) = resolver.resolve(&from_path, "hi").await else {
-      continue;
+    for (completed, node) in graph.nodes.iter().enumerate() {
+      plugins.report(&ReporterEvent::Progress {
+        module: node.id.to_string(),
+        completed: completed + 1,
+      });
+    }
+
+    println!("cache: {:?}", resolvers.cache().stats());
+
+    // Failing to write the cache only makes the next run slower
+    if let Err(error) = disk_cache.save(resolvers) {
+      plugins.report(&ReporterEvent::Warning {
+        message: format!("Could not write the cache: {}", error),
+        module: None,
+      });
+    }
+
+    run_hook(&node_instance, &plugins, LifecycleHook::BuildEnd).await;
+    plugins.report(&ReporterEvent::BuildEnd {
+      success: graph.errors.is_empty(),
+    });
+  };
+
+  run().await;
+
+  // Mimic running transformers
+  let input = TransformInput {
+    file_path: from_path.join("index.js"),
+    code: "import hi from 'hi';\n".to_string(),
+    ..Default::default()
+  };
+  match plugins.transform(&input).await {
+    Ok(output) => println!("transformed: {:?}", output),
+    Err(error) => println!("error: {}", error),
+  }
+
+  // Mimic loading a virtual module
+  let resolved_id = resolvers
+    .resolve(&from_path, "virtual:routes", &options)
+    .await
+    .map(|entry| entry.result.resolution.resolved_id());
+  match resolved_id {
+    Ok(Some(resolved_id)) => match plugins.load(&resolved_id).await {
+      Ok(output) => println!("loaded {}: {:?}", resolved_id, output),
+      Err(error) => println!("error: {}", error),
+    },
+    Ok(None) => println!("unresolved: virtual:routes"),
+    Err(error) => println!("error: {}", error),
+  }
+
+  if watch {
+    // The example project and the config (with its plugins) can change,
+    // build output and the cache are written while watching
+    let config_dir = env::current_dir().unwrap().join(config.config_path());
+    let roots = [
+      from_path.parent().unwrap().join("example"),
+      config_dir.parent().unwrap().to_path_buf(),
+    ];
+    let ignored = |path: &Path| {
+      path.starts_with(&cache_dir) || path.components().any(|part| part.as_os_str() == "target")
     };
-    println!("resolved: {:?}", file_path);
+
+    let watcher = FileWatcher::new(&roots).unwrap();
+    let mut on_events = watcher.subscribe();
+
+    // Ctrl-C drops the watcher, which ends the loop below so the
+    // plugins are torn down before exiting
+    tokio::spawn(async move {
+      tokio::signal::ctrl_c().await.ok();
+      drop(watcher);
+    });
+
+    // Run again whenever files change, only what depended
+    // on the changed files is resolved again
+    while let Some(mut events) = on_events.recv().await {
+      while let Ok(more_events) = on_events.try_recv() {
+        events.extend(more_events);
+      }
+      events.retain(|event| !ignored(event.path()));
+      if events.is_empty() {
+        continue;
+      }
+      resolvers.invalidate(&events).await;
+      run().await;
+    }
+  }
+
+  for error in node_instance.close().await {
+    eprintln!("{}", error);
   }
 }
 
@@ -46,3 +183,15 @@ fn main() {
     .unwrap()
     .block_on(main_async());
 }
+
+// Plugins hear about builds through their lifecycle hooks
+// as well as the reporter events
+async fn run_hook(
+  node_instance: &NodeInstance,
+  plugins: &PluginSet,
+  hook: LifecycleHook,
+) {
+  for message in node_instance.run_hook(hook).await {
+    plugins.report(&ReporterEvent::Warning { message, module: None });
+  }
+}
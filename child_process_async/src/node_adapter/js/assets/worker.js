@@ -6,7 +6,11 @@
     payload_as_json
 
   The message_ref is sent back to the sender to notify them that the
-  request has completed.
+  request has completed. The payload is just JSON and the two are 
+  terminated by a newline character.
+
+  A message_ref of "-" marks a notification, nothing is sent back
+  for those.
 
   The action_type is used to pick the callback to run.
 
@@ -23,19 +27,213 @@
     instances.
 */
 const { Socket } = require('net')
+const { createRequire } = require('node:module')
+const path = require('node:path')
+const { threadId } = require('node:worker_threads')
+
 const resolvers = {}
+const transformers = {}
+const loaders = {}
+const reporters = {}
+
+// Every plugin this worker loaded, with a name for errors, in load order
+const loaded = []
+
+// The message_ref of messages that get no response
+const NOTIFY_REF = '-'
+
+// The names of the hooks the host runs with run_hook
+const HOOKS = {
+  build_start: 'buildStart',
+  build_end: 'buildEnd',
+  teardown: 'teardown',
+}
+
+/*
+  Plugins export a function, or an object with that function named
+  after what it does (resolve, transform, load or report) and any of
+  these optional lifecycle hooks, which may return Promises:
+    setup(ctx)    once the plugin is loaded, ctx is { worker_id, options }
+    buildStart()  before each build
+    buildEnd()    after each build
+    teardown()    before the workers exit
+*/
+function call_plugin(plugin, name, arg) {
+  return typeof plugin === 'function' ? plugin(arg) : plugin[name](arg)
+}
 
-function load_resolver({ specifier }) {
-  resolvers[specifier] = require(specifier)
+async function setup_plugin(name, plugin, options) {
+  loaded.push({ name, plugin })
+  if (typeof plugin.setup === 'function') {
+    await plugin.setup({ worker_id: threadId, options: options ?? {} })
+  }
+}
+
+// Plugins are required from the config file, so packages are found in
+// the node_modules next to it rather than where Node.js was started.
+// ES modules are used through their default export when they have one,
+// require() gives back their namespace
+function require_plugin(specifier, config_path) {
+  const require_from_config = createRequire(path.resolve(config_path))
+  const exports = require_from_config(specifier)
+  const is_module = exports?.__esModule === true || exports?.[Symbol.toStringTag] === 'Module'
+  const esm = is_module && exports.default !== undefined
+  return {
+    exports: esm ? exports.default : exports,
+    esm,
+    resolved_path: require_from_config.resolve(specifier),
+  }
 }
 
-async function run_resolver({ resolver_key, from_path, specifier }) {
-  return await resolvers[resolver_key]({ from_path, specifier })
+function type_of(value) {
+  return value === null ? 'null' : typeof value
+}
+
+// Tells the host what the plugin looks like so it can check the
+// plugin before it is used, see PluginExports
+function describe_plugin(plugin, method, esm, factory) {
+  const members = {}
+  if (plugin !== null && plugin !== undefined) {
+    for (const name of [method, 'setup', 'buildStart', 'buildEnd', 'teardown', 'invalidate']) {
+      if (plugin[name] !== undefined) {
+        members[name] = type_of(plugin[name])
+      }
+    }
+  }
+  return { esm, factory, type: type_of(plugin), members }
+}
+
+// Resolvers are stored under the resolver_id the host picked, so one
+// plugin can be loaded many times with different options. Plugins
+// export the resolver itself, or a factory that makes one from the
+// options in the config: exports.factory = (options) => resolver.
+// Errors are sent back rather than thrown so the host can report them
+async function load_resolver({ resolver_id, specifier, config_path, options }) {
+  try {
+    const { exports, esm, resolved_path } = require_plugin(specifier, config_path)
+    const factory = typeof exports?.factory === 'function'
+    const plugin = factory ? await exports.factory(options ?? {}) : exports
+    resolvers[resolver_id] = plugin
+
+    // Plugins the host is going to turn down are not set up
+    if (typeof plugin === 'function' || typeof plugin?.resolve === 'function') {
+      await setup_plugin(`Resolver "${specifier}"`, plugin, options)
+    }
+    return {
+      resolved_path,
+      exports: describe_plugin(plugin, 'resolve', esm, factory),
+    }
+  } catch (error) {
+    return { error: error?.message ?? String(error) }
+  }
+}
+
+// A resolver that throws fails the resolution rather than the worker
+async function run_resolver({ resolver_id, from_path, specifier, options }) {
+  try {
+    return await call_plugin(resolvers[resolver_id], 'resolve', { from_path, specifier, options })
+  } catch (error) {
+    return { error: String(error) }
+  }
+}
+
+// Errors are sent back rather than thrown so the host can report them
+async function load_transformer({ specifier, config_path }) {
+  try {
+    transformers[specifier] = require_plugin(specifier, config_path).exports
+    await setup_plugin(`Transformer "${specifier}"`, transformers[specifier])
+  } catch (error) {
+    return { error: error?.message ?? String(error) }
+  }
+}
+
+async function run_transformer({ transformer_key, file_path, code, source_map, options }) {
+  try {
+    return await call_plugin(transformers[transformer_key], 'transform', { file_path, code, source_map, options })
+  } catch (error) {
+    return { error: String(error) }
+  }
+}
+
+// Errors are sent back rather than thrown so the host can report them
+async function load_loader({ specifier, config_path }) {
+  try {
+    loaders[specifier] = require_plugin(specifier, config_path).exports
+    await setup_plugin(`Loader "${specifier}"`, loaders[specifier])
+  } catch (error) {
+    return { error: error?.message ?? String(error) }
+  }
+}
+
+// Virtual modules come with a virtual_id of { namespace, id } instead of a file_path
+async function run_loader({ loader_key, file_path, virtual_id }) {
+  try {
+    return await call_plugin(loaders[loader_key], 'load', { file_path, virtual_id })
+  } catch (error) {
+    return { error: String(error) }
+  }
+}
+
+// Errors are sent back rather than thrown so the host can report them
+async function load_reporter({ specifier, config_path }) {
+  try {
+    reporters[specifier] = require_plugin(specifier, config_path).exports
+    await setup_plugin(`Reporter "${specifier}"`, reporters[specifier])
+  } catch (error) {
+    return { error: error?.message ?? String(error) }
+  }
+}
+
+// Events look like { type: "build_start" }, see ReporterEvent
+async function run_reporter({ reporter_key, event }) {
+  await call_plugin(reporters[reporter_key], 'report', event)
+}
+
+// Plugins may expose an "invalidate" function to drop their own
+// caches when files change, events look like { type: "create", path }
+async function invalidate({ resolver_id, events }) {
+  try {
+    const resolver = resolvers[resolver_id]
+    if (typeof resolver.invalidate === 'function') {
+      await resolver.invalidate(events)
+    }
+  } catch (error) {
+    // The host ignores the answer, a plugin failing to drop
+    // its caches is only logged
+    console.error(error)
+  }
+}
+
+// Runs the hook of every plugin at once, a hook failing doesn't stop
+// the others, the errors are sent back
+async function run_hook({ hook }) {
+  const name = HOOKS[hook]
+  const results = await Promise.allSettled(loaded.map(async ({ plugin }) => {
+    if (typeof plugin[name] === 'function') {
+      await plugin[name]()
+    }
+  }))
+
+  const errors = []
+  results.forEach((result, i) => {
+    if (result.status === 'rejected') {
+      errors.push(`${loaded[i].name} failed in ${name}: ${result.reason?.message ?? result.reason}`)
+    }
+  })
+  return { errors }
 }
 
 const actions = {
   load_resolver,
   run_resolver,
+  invalidate,
+  load_transformer,
+  run_transformer,
+  load_loader,
+  run_loader,
+  load_reporter,
+  run_reporter,
+  run_hook,
 }
 
 const client = new Socket();
@@ -46,7 +244,7 @@ let buffer = ''
 
 // When we get a message from the host split on a newline
 // character, parse the data and run the callback
-client.on('data', async function(data_str) {
+client.on('data', function(data_str) {
   for (const char of data_str.toString()) {
     if (char === '\n' && incoming_msg_ref === '') {
       incoming_msg_ref = buffer
@@ -67,8 +265,17 @@ client.on('data', async function(data_str) {
       buffer = ''
 
       setTimeout(async () => {
+        // Nobody is waiting on notifications so errors are only logged
+        if (msg_ref === NOTIFY_REF) {
+          try {
+            await actions[action](data)
+          } catch (error) {
+            console.error(error)
+          }
+          return
+        }
         const result = await actions[action](data)
-        const response = `${msg_ref}\n${JSON.stringify(result)}\n`
+        const response = `${msg_ref}\n${JSON.stringify(result ?? null)}\n`
         client.write(response)
       }, 0)
       continue
@@ -82,4 +289,4 @@ client.on('end', () => process.exit())
 client.on('close', () => process.exit());
 
 // @ts-expect-error
-client.connect('__MACH__PORT__', '127.0.0.1');
+client.connect('__MACH__PORT__', '127.0.0.1');
\ No newline at end of file
@@ -8,12 +8,21 @@
   a demo.
 
   Messages are load balanced between Node workers using round robin.
+  Notifications (notify/notify_all) are one-way, they are not
+  tracked in pending_messages and the worker does not respond.
+
+  Plugins loaded into the workers are referred to by an id handed
+  out here, so the same plugin can be loaded more than once. Their
+  lifecycle hooks are run on every worker with run_hook, close runs
+  the teardown hooks before Node.js is stopped.
 
   The glue code that is run within Node.js is piped in via stdin,
   but a final implementation would probably ship the JS glue code
   alongside the binary.
 */
 use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use serde::de::DeserializeOwned;
@@ -29,11 +38,16 @@ use tokio::sync::oneshot;
 use tokio::sync::Mutex;
 
 use super::spawn::spawn_node_js;
+use super::LifecycleHook;
+use super::NodeError;
 use super::NodeWorker;
+use super::RunHookRequest;
+use super::RunHookResponse;
 
 #[derive(Debug)]
 pub struct NodeInstance {
   send_to: Arc<Mutex<usize>>,
+  next_plugin_id: AtomicUsize,
   tx_shutdown: UnboundedSender<()>,
   workers: Vec<NodeWorker>,
 }
@@ -92,7 +106,7 @@ impl NodeInstance {
           line.pop();
           let value = std::mem::take(&mut line);
           
-          if incoming_msg_ref == "" {
+          if incoming_msg_ref.is_empty() {
             incoming_msg_ref = value;
             continue;
           }
@@ -107,10 +121,12 @@ impl NodeInstance {
             todo!();
           };
 
+          // The request may have been dropped while the worker handled
+          // it, e.g. a resolver cancelled by the concurrent pipeline
           if value == "undefined" {
-            listener.send("{}".to_string()).unwrap();
+            listener.send("{}".to_string()).ok();
           } else {
-            listener.send(value).unwrap();
+            listener.send(value).ok();
           }
         }
       });
@@ -135,16 +151,22 @@ impl NodeInstance {
 
     return NodeInstance {
       send_to: Arc::new(Mutex::new(0)),
+      next_plugin_id: AtomicUsize::new(0),
       tx_shutdown,
       workers,
     };
   }
 
+  // For a plugin about to be loaded into the workers, the same on every worker
+  pub fn new_plugin_id(&self) -> usize {
+    return self.next_plugin_id.fetch_add(1, Ordering::Relaxed);
+  }
+
   pub async fn send<T, U>(
     &self,
     action: &str,
     data: &T,
-  ) -> Result<U, ()>
+  ) -> Result<U, NodeError>
   where
     T: ?Sized + Serialize,
     U: DeserializeOwned,
@@ -157,7 +179,7 @@ impl NodeInstance {
         *send_to = 0;
       }
 
-      let send_index = (*send_to).clone();
+      let send_index = *send_to;
       *send_to += 1;
       send_index
     };
@@ -165,7 +187,44 @@ impl NodeInstance {
     self.workers[send_to].send(action, data).await
   }
 
-  pub async fn send_all<T>(
+  pub async fn send_all<T, U>(
+    &self,
+    action: &str,
+    data: &T,
+  ) -> Result<Vec<U>, NodeError>
+  where
+    T: ?Sized + Serialize,
+    U: DeserializeOwned,
+  {
+    let mut responses = vec![];
+    for worker in &self.workers {
+      responses.push(worker.send::<T, U>(action, data).await?);
+    }
+    return Ok(responses);
+  }
+
+  // Sends to the given worker without waiting for it, the worker
+  // handles these in the order they were sent. Fails when there is
+  // no such worker
+  pub fn notify<T>(
+    &self,
+    worker: usize,
+    action: &str,
+    data: &T,
+  ) -> Result<(), ()>
+  where
+    T: ?Sized + Serialize,
+  {
+    let Some(worker) = self.workers.get(worker) else {
+      return Err(());
+    };
+    worker.notify(action, data);
+    return Ok(());
+  }
+
+  // Sends to every worker without waiting for them, fails when
+  // there are no workers
+  pub fn notify_all<T>(
     &self,
     action: &str,
     data: &T,
@@ -173,14 +232,36 @@ impl NodeInstance {
   where
     T: ?Sized + Serialize,
   {
+    if self.workers.is_empty() {
+      return Err(());
+    }
     for worker in &self.workers {
-      if worker.send::<T, serde_json::Value>(action, data).await.is_err() {
-        return Err(());
-      }
+      worker.notify(action, data);
     }
     return Ok(());
   }
 
+  // Runs the hook of every plugin on every worker and waits for them
+  // to finish, returns the errors the hooks threw
+  pub async fn run_hook(
+    &self,
+    hook: LifecycleHook,
+  ) -> Vec<String> {
+    let req = RunHookRequest { hook };
+    let Ok(responses) = self.send_all::<_, RunHookResponse>("run_hook", &req).await else {
+      return vec![format!("No response from the workers running {}", hook)];
+    };
+    return responses.into_iter().flat_map(|response| response.errors).collect();
+  }
+
+  // Lets plugins clean up before stopping Node.js, dropping the
+  // instance stops it without running the teardown hooks
+  pub async fn close(&self) -> Vec<String> {
+    let errors = self.run_hook(LifecycleHook::Teardown).await;
+    self.shutdown().ok();
+    return errors;
+  }
+
   pub fn shutdown(&self) -> Result<(), ()> {
     if self.tx_shutdown.send(()).is_err() {
       return Err(());
@@ -17,12 +17,30 @@ pub struct NodeWorker {
   pub pending_messages: Arc<Mutex<HashMap<String, oneshot::Sender<String>>>>,
 }
 
+// Sent in place of the message_ref for messages that get no response
+pub const NOTIFY_REF: &str = "-";
+
 impl NodeWorker {
+  // Sends without waiting, nothing comes back from the worker
+  pub fn notify<T>(
+    &self,
+    action: &str,
+    data: &T,
+  ) where
+    T: ?Sized + Serialize,
+  {
+    let data = serde_json::to_string::<T>(data).unwrap();
+    self
+      .tx_to_child
+      .send((NOTIFY_REF.to_string(), action.to_string(), data))
+      .ok();
+  }
+
   pub async fn send<T, U>(
     &self,
     action: &str,
     data: &T,
-  ) -> Result<U, ()>
+  ) -> Result<U, NodeError>
   where
     T: ?Sized + Serialize,
     U: DeserializeOwned,
@@ -38,11 +56,30 @@ impl NodeWorker {
       .unwrap();
 
     let Ok(result) = rx.await else {
-      return Err(());
+      return Err(NodeError::NoResponse);
     };
+    return serde_json::from_str::<U>(&result)
+      .map_err(|error| NodeError::InvalidResponse(error.to_string()));
+  }
+}
 
-    let result = serde_json::from_str::<U>(&result).unwrap();
+// Why a request sent to a worker has no usable response
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeError {
+  // The worker went away before it responded
+  NoResponse,
+  // The response doesn't have the expected shape, with why it couldn't be read
+  InvalidResponse(String),
+}
 
-    return Ok(result);
+impl std::fmt::Display for NodeError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      NodeError::NoResponse => write!(f, "No response"),
+      NodeError::InvalidResponse(error) => write!(f, "Invalid response: {}", error),
+    }
   }
 }
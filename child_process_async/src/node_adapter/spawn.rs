@@ -23,7 +23,7 @@ pub async fn spawn_node_js(
   // Execute the glue code within Node.js
   let mut stdin = child.stdin.take().unwrap();
   let script = get_js(port, worker_count);
-  stdin.write(script.as_bytes()).await.unwrap();
+  stdin.write_all(script.as_bytes()).await.unwrap();
   stdin.flush().await.unwrap();
   drop(stdin);
 
@@ -0,0 +1,346 @@
+/*
+  Persists the ResolveCache of a ResolverPipeline to disk so warm runs
+  can skip resolving altogether (and the round trips to Node.js).
+
+  Each entry is stored with the identity of the resolvers that took
+  part in producing it and the modification times of the files it
+  depends on. When loading, an entry is dropped if one of those
+  resolvers changed (version, options or plugin contents) or if one
+  of those files was changed, deleted or created since it was saved.
+  The files next to a resolved file that share its name are listed
+  too, the entry is dropped if they differ as a new sibling may now
+  take precedence (see ResolveCache).
+*/
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use serde::Serialize;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::public::Resolution;
+use crate::public::ResolveResult;
+
+use super::CacheKey;
+use super::PipelineResult;
+use super::ResolverPipeline;
+
+#[derive(Debug)]
+pub struct DiskCache {
+  file_path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskCacheFile {
+  entries: Vec<DiskCacheEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskCacheEntry {
+  identity: String,
+  key: CacheKey,
+  result: PipelineResult,
+  files: Vec<FileStamp>,
+  siblings: Vec<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileStamp {
+  path: PathBuf,
+  // None when the file did not exist
+  modified: Option<SystemTime>,
+}
+
+impl DiskCache {
+  pub fn new(cache_dir: &Path) -> Self {
+    Self {
+      file_path: cache_dir.join("resolver_cache.json"),
+    }
+  }
+
+  // Seeds the pipeline's cache with the entries that are still valid,
+  // returns how many were loaded
+  pub fn load(
+    &self,
+    pipeline: &ResolverPipeline,
+  ) -> usize {
+    let Ok(contents) = fs::read_to_string(&self.file_path) else {
+      return 0;
+    };
+    let Ok(cache_file) = serde_json::from_str::<DiskCacheFile>(&contents) else {
+      return 0;
+    };
+
+    let mut loaded = 0;
+    let generation = pipeline.cache().generation();
+
+    for entry in cache_file.entries {
+      let identity = pipeline_identity(pipeline, &entry.result);
+      if identity.as_ref() != Some(&entry.identity) {
+        continue;
+      }
+      if entry.files.iter().any(|stamp| stamp.is_stale()) {
+        continue;
+      }
+      if siblings(&entry.result.result) != entry.siblings {
+        continue;
+      }
+      if pipeline.cache().insert(entry.key, entry.result, generation) {
+        loaded += 1;
+      }
+    }
+
+    return loaded;
+  }
+
+  pub fn save(
+    &self,
+    pipeline: &ResolverPipeline,
+  ) -> std::io::Result<()> {
+    let mut entries = Vec::<DiskCacheEntry>::new();
+
+    for (key, result) in pipeline.cache().entries() {
+      let Some(identity) = pipeline_identity(pipeline, &result) else {
+        continue;
+      };
+      let files = dependencies(&result.result)
+        .map(|file_path| FileStamp::new(file_path))
+        .collect();
+      let siblings = siblings(&result.result);
+
+      entries.push(DiskCacheEntry {
+        identity,
+        key,
+        result,
+        files,
+        siblings,
+      });
+    }
+
+    if let Some(cache_dir) = self.file_path.parent() {
+      fs::create_dir_all(cache_dir)?;
+    }
+    fs::write(&self.file_path, serde_json::to_string(&DiskCacheFile { entries })?)?;
+    return Ok(());
+  }
+}
+
+impl FileStamp {
+  fn new(file_path: &Path) -> Self {
+    Self {
+      path: file_path.to_path_buf(),
+      modified: modified(file_path),
+    }
+  }
+
+  fn is_stale(&self) -> bool {
+    modified(&self.path) != self.modified
+  }
+}
+
+fn pipeline_identity(
+  pipeline: &ResolverPipeline,
+  result: &PipelineResult,
+) -> Option<String> {
+  let identity = pipeline.identity(result.resolver_name.as_deref())?;
+  return Some(format!("{:016x}", xxh3_64(identity.as_bytes())));
+}
+
+// Every file the result depends on, whether it exists or not
+fn dependencies(result: &ResolveResult) -> impl Iterator<Item = &PathBuf> {
+  let resolved_path = match &result.resolution {
+    Resolution::Path(file_path) => Some(file_path),
+    _ => None,
+  };
+
+  result
+    .invalidate_on_file_change
+    .iter()
+    .chain(&result.invalidate_on_file_create)
+    .chain(resolved_path)
+}
+
+// The files beside the resolved file with the same name but another
+// extension, "index.ts" and "index.mjs" for "index.js"
+fn siblings(result: &ResolveResult) -> Vec<PathBuf> {
+  let Resolution::Path(file_path) = &result.resolution else {
+    return vec![];
+  };
+  let Some(Ok(dir)) = file_path.parent().map(fs::read_dir) else {
+    return vec![];
+  };
+
+  let stem = file_path.with_extension("");
+  let mut siblings = dir
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|path| path != file_path && path.with_extension("") == stem)
+    .collect::<Vec<PathBuf>>();
+  siblings.sort();
+  return siblings;
+}
+
+fn modified(file_path: &Path) -> Option<SystemTime> {
+  fs::metadata(file_path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env;
+  use std::fs::File;
+  use std::process;
+  use std::time::Duration;
+
+  use async_trait::async_trait;
+
+  use crate::public::ResolveError;
+  use crate::public::ResolveOptions;
+  use crate::public::Resolver;
+
+  use super::*;
+
+  // Resolves every specifier to the same file
+  #[derive(Debug)]
+  struct TestResolver {
+    file_path: PathBuf,
+    identity: Option<&'static str>,
+  }
+
+  #[async_trait]
+  impl Resolver for TestResolver {
+    async fn resolve(
+      &self,
+      _from_path: &Path,
+      _specifier: &str,
+      _options: &ResolveOptions,
+    ) -> Result<ResolveResult, ResolveError> {
+      return Ok(ResolveResult::path(self.file_path.clone()));
+    }
+
+    fn identity(&self) -> Option<String> {
+      self.identity.map(|identity| identity.to_string())
+    }
+  }
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("mach-disk-cache-{}-{}", process::id(), name));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    return dir;
+  }
+
+  fn pipeline(
+    file_path: &Path,
+    identity: Option<&'static str>,
+  ) -> ResolverPipeline {
+    let mut pipeline = ResolverPipeline::new(false);
+    let file_path = file_path.to_path_buf();
+    pipeline.add(
+      "test",
+      0,
+      TestResolver {
+        file_path,
+        identity,
+      },
+    );
+    return pipeline;
+  }
+
+  // Runs a resolve with a fresh pipeline and saves its cache
+  async fn save(
+    disk_cache: &DiskCache,
+    dir: &Path,
+    file_path: &Path,
+    identity: Option<&'static str>,
+  ) -> PipelineResult {
+    let pipeline = pipeline(file_path, identity);
+    let result = pipeline
+      .resolve(dir, "./a", &ResolveOptions::default())
+      .await
+      .unwrap();
+    disk_cache.save(&pipeline).unwrap();
+    return result;
+  }
+
+  #[tokio::test]
+  async fn entries_survive_a_round_trip() {
+    let dir = temp_dir("round-trip");
+    let file_path = dir.join("a.js");
+    fs::write(&file_path, "").unwrap();
+    let disk_cache = DiskCache::new(&dir.join("cache"));
+    let result = save(&disk_cache, &dir, &file_path, Some("test@1")).await;
+
+    let pipeline = pipeline(&file_path, Some("test@1"));
+    assert_eq!(disk_cache.load(&pipeline), 1);
+    let key = CacheKey::new(&dir, "./a", &ResolveOptions::default());
+    assert_eq!(pipeline.cache().get(&key), Some(result));
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[tokio::test]
+  async fn entries_of_changed_resolvers_are_dropped() {
+    let dir = temp_dir("identity");
+    let file_path = dir.join("a.js");
+    fs::write(&file_path, "").unwrap();
+    let disk_cache = DiskCache::new(&dir.join("cache"));
+    save(&disk_cache, &dir, &file_path, Some("test@1")).await;
+
+    assert_eq!(disk_cache.load(&pipeline(&file_path, Some("test@2"))), 0);
+    assert_eq!(disk_cache.load(&pipeline(&file_path, None)), 0);
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[tokio::test]
+  async fn entries_of_changed_files_are_dropped() {
+    let dir = temp_dir("files");
+    let file_path = dir.join("a.js");
+    fs::write(&file_path, "").unwrap();
+    let disk_cache = DiskCache::new(&dir.join("cache"));
+    save(&disk_cache, &dir, &file_path, Some("test@1")).await;
+
+    let modified = SystemTime::now() + Duration::from_secs(60);
+    File::options()
+      .write(true)
+      .open(&file_path)
+      .unwrap()
+      .set_modified(modified)
+      .unwrap();
+    assert_eq!(disk_cache.load(&pipeline(&file_path, Some("test@1"))), 0);
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[tokio::test]
+  async fn resolvers_without_an_identity_are_not_saved() {
+    let dir = temp_dir("no-identity");
+    let file_path = dir.join("a.js");
+    fs::write(&file_path, "").unwrap();
+    let disk_cache = DiskCache::new(&dir.join("cache"));
+    save(&disk_cache, &dir, &file_path, None).await;
+
+    let contents = fs::read_to_string(dir.join("cache").join("resolver_cache.json")).unwrap();
+    let cache_file = serde_json::from_str::<DiskCacheFile>(&contents).unwrap();
+    assert!(cache_file.entries.is_empty());
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[tokio::test]
+  async fn entries_are_dropped_when_a_sibling_is_created() {
+    let dir = temp_dir("siblings");
+    let file_path = dir.join("a.js");
+    fs::write(&file_path, "").unwrap();
+    let disk_cache = DiskCache::new(&dir.join("cache"));
+    save(&disk_cache, &dir, &file_path, Some("test@1")).await;
+    assert_eq!(disk_cache.load(&pipeline(&file_path, Some("test@1"))), 1);
+
+    fs::write(dir.join("a.ts"), "").unwrap();
+    assert_eq!(disk_cache.load(&pipeline(&file_path, Some("test@1"))), 0);
+
+    fs::remove_dir_all(&dir).ok();
+  }
+}
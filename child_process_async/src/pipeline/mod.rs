@@ -0,0 +1,7 @@
+mod disk_cache;
+mod resolve_cache;
+mod resolver_pipeline;
+
+pub use crate::pipeline::disk_cache::*;
+pub use crate::pipeline::resolve_cache::*;
+pub use crate::pipeline::resolver_pipeline::*;
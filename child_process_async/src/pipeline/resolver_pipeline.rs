@@ -0,0 +1,422 @@
+/*
+  Runs a set of resolvers in priority order (lowest first, ties keep
+  the order they were added in) and stops at the first definitive
+  answer. A resolver declines a specifier by returning
+  Resolution::Unresolved, which passes it on to the next resolver.
+  Errors are definitive and stop the pipeline.
+
+  In concurrent mode every resolver is queried at once, the answers
+  are still taken in priority order so the result is the same as
+  running them one after another. This trades extra work in lower
+  priority resolvers for latency when higher priority resolvers are
+  slow (e.g. round trips to Node.js)
+
+  Answers are cached (see ResolveCache). The files that resolvers
+  which declined depended on are merged into the final answer so the
+  cache entry is also dropped when a declining resolver would now
+  answer differently
+*/
+use std::path::Path;
+
+use futures::stream::FuturesOrdered;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::public::FileEvent;
+use crate::public::Resolution;
+use crate::public::ResolveError;
+use crate::public::ResolveOptions;
+use crate::public::ResolveResult;
+use crate::public::Resolver;
+
+use super::CacheKey;
+use super::ResolveCache;
+
+#[derive(Debug)]
+pub struct ResolverPipeline {
+  concurrent: bool,
+  cache: ResolveCache,
+  resolvers: Vec<PipelineEntry>,
+}
+
+#[derive(Debug)]
+struct PipelineEntry {
+  name: String,
+  priority: i32,
+  identity: Option<String>,
+  resolver: Box<dyn Resolver>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PipelineResult {
+  // Name of the resolver that answered, None when no resolver did
+  pub resolver_name: Option<String>,
+  pub result: ResolveResult,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineError {
+  pub resolver_name: String,
+  pub error: ResolveError,
+}
+
+impl ResolverPipeline {
+  pub fn new(concurrent: bool) -> Self {
+    Self {
+      concurrent,
+      cache: ResolveCache::new(),
+      resolvers: vec![],
+    }
+  }
+
+  pub fn cache(&self) -> &ResolveCache {
+    &self.cache
+  }
+
+  // The name must be unique, answers and cache identities refer to the resolver by it
+  pub fn add<R: Resolver + 'static>(
+    &mut self,
+    name: &str,
+    priority: i32,
+    resolver: R,
+  ) {
+    let index = self
+      .resolvers
+      .partition_point(|entry| entry.priority <= priority);
+
+    self.resolvers.insert(
+      index,
+      PipelineEntry {
+        name: name.to_string(),
+        priority,
+        identity: resolver.identity(),
+        resolver: Box::new(resolver),
+      },
+    );
+  }
+
+  // Identifies the resolvers that took part in producing an answer,
+  // every resolver up to and including the one that answered. None
+  // if any of them can't be identified
+  pub fn identity(
+    &self,
+    resolver_name: Option<&str>,
+  ) -> Option<String> {
+    let mut identity = Vec::<String>::new();
+
+    for entry in &self.resolvers {
+      identity.push(format!("{}={}", entry.name, entry.identity.as_ref()?));
+      if Some(entry.name.as_str()) == resolver_name {
+        break;
+      }
+    }
+
+    return Some(identity.join("\n"));
+  }
+
+  pub async fn resolve(
+    &self,
+    from_path: &Path,
+    specifier: &str,
+    options: &ResolveOptions,
+  ) -> Result<PipelineResult, PipelineError> {
+    let key = CacheKey::new(from_path, specifier, options);
+    let generation = self.cache.generation();
+    if let Some(result) = self.cache.get(&key) {
+      return Ok(result);
+    }
+
+    let result = match self.concurrent {
+      true => self.resolve_concurrent(from_path, specifier, options).await?,
+      false => self.resolve_sequential(from_path, specifier, options).await?,
+    };
+
+    self.cache.insert(key, result.clone(), generation);
+    return Ok(result);
+  }
+
+  // Drops cached answers that depend on the changed files and
+  // lets every resolver know about the changes
+  pub async fn invalidate(
+    &self,
+    events: &[FileEvent],
+  ) {
+    for event in events {
+      self.cache.invalidate(event);
+    }
+    for entry in &self.resolvers {
+      entry.resolver.invalidate(events).await;
+    }
+  }
+
+  async fn resolve_sequential(
+    &self,
+    from_path: &Path,
+    specifier: &str,
+    options: &ResolveOptions,
+  ) -> Result<PipelineResult, PipelineError> {
+    let mut declined = ResolveResult::default();
+
+    for entry in &self.resolvers {
+      let result = entry.resolver.resolve(from_path, specifier, options).await;
+      if let Some(answer) = entry.to_answer(result, &mut declined) {
+        return answer;
+      }
+    }
+
+    return Ok(PipelineResult::unresolved(declined));
+  }
+
+  async fn resolve_concurrent(
+    &self,
+    from_path: &Path,
+    specifier: &str,
+    options: &ResolveOptions,
+  ) -> Result<PipelineResult, PipelineError> {
+    // Polls every resolver at once but yields their results in the
+    // order they were pushed. Returning early drops (cancels) the
+    // resolvers further down that are still running
+    let mut on_results = self
+      .resolvers
+      .iter()
+      .map(|entry| async move {
+        let result = entry.resolver.resolve(from_path, specifier, options).await;
+        (entry, result)
+      })
+      .collect::<FuturesOrdered<_>>();
+
+    let mut declined = ResolveResult::default();
+
+    while let Some((entry, result)) = on_results.next().await {
+      if let Some(answer) = entry.to_answer(result, &mut declined) {
+        return answer;
+      }
+    }
+
+    return Ok(PipelineResult::unresolved(declined));
+  }
+}
+
+impl PipelineEntry {
+  // None when the resolver declined and the next one should be tried,
+  // what the declining resolver depended on is collected in "declined"
+  fn to_answer(
+    &self,
+    result: Result<ResolveResult, ResolveError>,
+    declined: &mut ResolveResult,
+  ) -> Option<Result<PipelineResult, PipelineError>> {
+    match result {
+      Ok(result) if result.resolution == Resolution::Unresolved => {
+        declined.invalidate_on_file_change.extend(result.invalidate_on_file_change);
+        declined.invalidate_on_file_create.extend(result.invalidate_on_file_create);
+        None
+      }
+      Ok(mut result) => {
+        result
+          .invalidate_on_file_change
+          .append(&mut declined.invalidate_on_file_change);
+        result
+          .invalidate_on_file_create
+          .append(&mut declined.invalidate_on_file_create);
+        Some(Ok(PipelineResult {
+          resolver_name: Some(self.name.clone()),
+          result,
+        }))
+      }
+      Err(error) => Some(Err(PipelineError {
+        resolver_name: self.name.clone(),
+        error,
+      })),
+    }
+  }
+}
+
+impl PipelineResult {
+  fn unresolved(declined: ResolveResult) -> Self {
+    Self {
+      resolver_name: None,
+      result: declined,
+    }
+  }
+}
+
+impl std::fmt::Display for PipelineError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    write!(f, "{} (resolver \"{}\")", self.error, self.resolver_name)
+  }
+}
+
+impl std::error::Error for PipelineError {}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+  use std::time::Duration;
+
+  use async_trait::async_trait;
+
+  use super::*;
+
+  #[derive(Debug)]
+  enum Answer {
+    // Declines and reports the file the decision depended on
+    Decline(&'static str),
+    Path(&'static str),
+    Fail(&'static str),
+  }
+
+  #[derive(Debug)]
+  struct TestResolver {
+    answer: Answer,
+    delay_ms: u64,
+  }
+
+  #[async_trait]
+  impl Resolver for TestResolver {
+    async fn resolve(
+      &self,
+      _from_path: &Path,
+      _specifier: &str,
+      _options: &ResolveOptions,
+    ) -> Result<ResolveResult, ResolveError> {
+      tokio::time::sleep(Duration::from_millis(self.delay_ms)).await;
+      return match self.answer {
+        Answer::Decline(file_path) => Ok(ResolveResult {
+          invalidate_on_file_change: vec![PathBuf::from(file_path)],
+          ..Default::default()
+        }),
+        Answer::Path(file_path) => Ok(ResolveResult::path(PathBuf::from(file_path))),
+        Answer::Fail(message) => Err(ResolveError::Failed(message.to_string())),
+      };
+    }
+  }
+
+  fn pipeline(
+    concurrent: bool,
+    resolvers: Vec<(&str, i32, Answer, u64)>,
+  ) -> ResolverPipeline {
+    let mut pipeline = ResolverPipeline::new(concurrent);
+    for (name, priority, answer, delay_ms) in resolvers {
+      pipeline.add(name, priority, TestResolver { answer, delay_ms });
+    }
+    return pipeline;
+  }
+
+  async fn resolve(pipeline: &ResolverPipeline) -> Result<PipelineResult, PipelineError> {
+    return pipeline
+      .resolve(Path::new("/"), "./a.js", &ResolveOptions::default())
+      .await;
+  }
+
+  #[tokio::test]
+  async fn lowest_priority_answers_first() {
+    let pipeline = pipeline(
+      false,
+      vec![
+        ("late", 2, Answer::Path("/late.js"), 0),
+        ("early", 1, Answer::Path("/early.js"), 0),
+      ],
+    );
+    let result = resolve(&pipeline).await.unwrap();
+    assert_eq!(result.resolver_name.as_deref(), Some("early"));
+    assert_eq!(
+      result.result.resolution,
+      Resolution::Path(PathBuf::from("/early.js"))
+    );
+  }
+
+  #[tokio::test]
+  async fn ties_keep_the_order_they_were_added_in() {
+    let pipeline = pipeline(
+      false,
+      vec![
+        ("first", 0, Answer::Path("/first.js"), 0),
+        ("second", 0, Answer::Path("/second.js"), 0),
+      ],
+    );
+    assert_eq!(
+      resolve(&pipeline).await.unwrap().resolver_name.as_deref(),
+      Some("first")
+    );
+  }
+
+  #[tokio::test]
+  async fn declining_passes_on_and_keeps_dependencies() {
+    let pipeline = pipeline(
+      false,
+      vec![
+        ("declines", 0, Answer::Decline("/declined.json"), 0),
+        ("answers", 1, Answer::Path("/a.js"), 0),
+      ],
+    );
+    let result = resolve(&pipeline).await.unwrap();
+    assert_eq!(result.resolver_name.as_deref(), Some("answers"));
+    assert_eq!(
+      result.result.invalidate_on_file_change,
+      vec![PathBuf::from("/declined.json")]
+    );
+  }
+
+  #[tokio::test]
+  async fn unresolved_when_every_resolver_declines() {
+    let pipeline = pipeline(
+      false,
+      vec![("declines", 0, Answer::Decline("/declined.json"), 0)],
+    );
+    let result = resolve(&pipeline).await.unwrap();
+    assert_eq!(result.resolver_name, None);
+    assert_eq!(result.result.resolution, Resolution::Unresolved);
+    assert_eq!(
+      result.result.invalidate_on_file_change,
+      vec![PathBuf::from("/declined.json")]
+    );
+  }
+
+  #[tokio::test]
+  async fn errors_stop_the_pipeline() {
+    let pipeline = pipeline(
+      false,
+      vec![
+        ("fails", 0, Answer::Fail("nope"), 0),
+        ("answers", 1, Answer::Path("/a.js"), 0),
+      ],
+    );
+    let error = resolve(&pipeline).await.unwrap_err();
+    assert_eq!(error.resolver_name, "fails");
+    assert_eq!(error.error, ResolveError::Failed("nope".to_string()));
+  }
+
+  #[tokio::test]
+  async fn concurrent_answers_in_priority_order() {
+    // The slow resolver comes first so the fast one finishes before it
+    let answers = pipeline(
+      true,
+      vec![
+        ("slow", 0, Answer::Path("/slow.js"), 50),
+        ("fast", 1, Answer::Path("/fast.js"), 0),
+      ],
+    );
+    assert_eq!(
+      resolve(&answers).await.unwrap().resolver_name.as_deref(),
+      Some("slow")
+    );
+
+    let declines = pipeline(
+      true,
+      vec![
+        ("slow", 0, Answer::Decline("/declined.json"), 50),
+        ("fast", 1, Answer::Path("/fast.js"), 0),
+      ],
+    );
+    let result = resolve(&declines).await.unwrap();
+    assert_eq!(result.resolver_name.as_deref(), Some("fast"));
+    assert_eq!(
+      result.result.invalidate_on_file_change,
+      vec![PathBuf::from("/declined.json")]
+    );
+  }
+}
@@ -1,5 +1,7 @@
 mod default_resolver;
 mod node_proxy;
+mod workspace_resolver;
 
 pub use crate::plugins::default_resolver::*;
 pub use crate::plugins::node_proxy::*;
+pub use crate::plugins::workspace_resolver::*;
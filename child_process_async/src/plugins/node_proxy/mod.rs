@@ -1,3 +1,12 @@
+mod loader;
+mod plugin_exports;
+mod plugin_hash;
+mod reporter;
 mod resolver;
+mod transformer;
 
+pub use crate::plugins::node_proxy::loader::*;
+pub use crate::plugins::node_proxy::plugin_exports::*;
+pub use crate::plugins::node_proxy::reporter::*;
 pub use crate::plugins::node_proxy::resolver::*;
+pub use crate::plugins::node_proxy::transformer::*;
@@ -0,0 +1,216 @@
+/*
+  What a JS plugin exports, as described by the worker that loaded it.
+  The description is checked when the plugin is loaded so a plugin
+  with the wrong shape fails straight away, naming the plugin and the
+  problem, rather than on the first time it is run.
+
+  Plugins export a function, or an object with that function as a
+  method and optional lifecycle hooks. ES modules are described by
+  their default export and factories by the plugin they return.
+*/
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+// The members of the object form other than the plugin's main method
+const HOOKS: [&str; 5] = ["setup", "buildStart", "buildEnd", "teardown", "invalidate"];
+
+/*
+  {
+    esm: boolean,      // the plugin is the default export of an ES module
+    factory: boolean,  // the plugin was returned by exports.factory(options)
+    type: string,      // typeof the plugin
+    members: {},       // typeof the method and hooks the plugin has
+  }
+*/
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PluginExports {
+  pub esm: bool,
+  pub factory: bool,
+  #[serde(rename = "type")]
+  pub type_of: String,
+  pub members: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PluginLoadError {
+  // Requiring the plugin, its factory or its setup hook threw
+  Failed { specifier: String, message: String },
+  // The plugin doesn't have the shape the plugin kind needs
+  InvalidExports { specifier: String, problem: String },
+  // The plugin could not be reached, e.g. a Node.js worker went away
+  Unreachable { specifier: String },
+}
+
+impl PluginExports {
+  // Checks the plugin is a function or an object with the method,
+  // and that the hooks it has are functions
+  pub fn check(
+    &self,
+    method: &str,
+  ) -> Result<(), String> {
+    let export = match (self.esm, self.factory) {
+      (_, true) => "exports.factory returned",
+      (true, false) => "the default export is",
+      (false, false) => "the module exports",
+    };
+
+    match self.type_of.as_str() {
+      "function" => {}
+      "object" => match self.members.get(method).map(String::as_str) {
+        Some("function") => {}
+        Some(type_of) => {
+          return Err(format!(
+            "\"{}\" is {}, expected a function",
+            method,
+            with_article(type_of)
+          ))
+        }
+        None => {
+          return Err(format!(
+            "{} an object without a \"{}\" method",
+            export, method
+          ))
+        }
+      },
+      type_of => {
+        return Err(format!(
+          "{} {}, expected a function or an object with a \"{}\" method",
+          export,
+          with_article(type_of),
+          method
+        ))
+      }
+    }
+
+    for hook in HOOKS {
+      match self.members.get(hook).map(String::as_str) {
+        None | Some("function") => {}
+        Some(type_of) => {
+          return Err(format!(
+            "the \"{}\" hook is {}, expected a function",
+            hook,
+            with_article(type_of)
+          ))
+        }
+      }
+    }
+
+    return Ok(());
+  }
+}
+
+fn with_article(type_of: &str) -> String {
+  return match type_of {
+    "undefined" | "null" => type_of.to_string(),
+    _ if type_of.starts_with(['a', 'e', 'i', 'o', 'u']) => format!("an {}", type_of),
+    _ => format!("a {}", type_of),
+  };
+}
+
+impl Display for PluginLoadError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      PluginLoadError::Failed { specifier, message } => {
+        write!(f, "Failed to load plugin \"{}\": {}", specifier, message)
+      }
+      PluginLoadError::InvalidExports { specifier, problem } => {
+        write!(f, "Plugin \"{}\" can't be used: {}", specifier, problem)
+      }
+      PluginLoadError::Unreachable { specifier } => {
+        write!(f, "No response loading plugin \"{}\"", specifier)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn exports(json: &str) -> PluginExports {
+    return serde_json::from_str(json).unwrap();
+  }
+
+  #[test]
+  fn functions_and_objects_with_the_method_can_be_used() {
+    let function =
+      exports(r#"{ "esm": false, "factory": false, "type": "function", "members": {} }"#);
+    assert_eq!(function.check("resolve"), Ok(()));
+
+    let object = exports(
+      r#"{ "esm": true, "factory": false, "type": "object",
+           "members": { "transform": "function", "setup": "function", "teardown": "function" } }"#,
+    );
+    assert_eq!(object.check("transform"), Ok(()));
+  }
+
+  #[test]
+  fn names_where_the_plugin_came_from() {
+    let mut plugin =
+      exports(r#"{ "esm": false, "factory": false, "type": "object", "members": {} }"#);
+    assert_eq!(
+      plugin.check("load"),
+      Err("the module exports an object without a \"load\" method".to_string())
+    );
+
+    plugin.esm = true;
+    assert_eq!(
+      plugin.check("load"),
+      Err("the default export is an object without a \"load\" method".to_string())
+    );
+
+    plugin.factory = true;
+    assert_eq!(
+      plugin.check("load"),
+      Err("exports.factory returned an object without a \"load\" method".to_string())
+    );
+  }
+
+  #[test]
+  fn rejects_plugins_of_the_wrong_type() {
+    let cases = [
+      ("undefined", "the module exports undefined"),
+      ("null", "the module exports null"),
+      ("string", "the module exports a string"),
+      ("number", "the module exports a number"),
+    ];
+    for (type_of, expected) in cases {
+      let mut plugin =
+        exports(r#"{ "esm": false, "factory": false, "type": "object", "members": {} }"#);
+      plugin.type_of = type_of.to_string();
+      assert_eq!(
+        plugin.check("report"),
+        Err(format!(
+          "{}, expected a function or an object with a \"report\" method",
+          expected
+        ))
+      );
+    }
+  }
+
+  #[test]
+  fn methods_and_hooks_must_be_functions() {
+    let method = exports(
+      r#"{ "esm": false, "factory": false, "type": "object", "members": { "resolve": "object" } }"#,
+    );
+    assert_eq!(
+      method.check("resolve"),
+      Err("\"resolve\" is an object, expected a function".to_string())
+    );
+
+    // Hooks are checked on plugins that are functions too
+    let hook = exports(
+      r#"{ "esm": false, "factory": false, "type": "function", "members": { "buildEnd": "boolean" } }"#,
+    );
+    assert_eq!(
+      hook.check("resolve"),
+      Err("the \"buildEnd\" hook is a boolean, expected a function".to_string())
+    );
+  }
+}
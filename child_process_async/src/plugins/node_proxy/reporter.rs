@@ -0,0 +1,113 @@
+/*
+  This is a "Plugin Proxy" that runs functions in the
+  Node workers remotely via the NodeInstance, translating
+  the requests/responses to match the interface of the
+  internal "Reporter" trait
+
+  Events are sent as notifications to a single worker so they
+  arrive in order and nothing waits on them
+*/
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::node_adapter::NodeError;
+use crate::node_adapter::NodeInstance;
+use crate::public::Reporter;
+use crate::public::ReporterEvent;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::PluginLoadError;
+
+// Every worker loads the reporter but only this one is sent events
+const REPORTER_WORKER: usize = 0;
+
+#[derive(Debug)]
+pub struct ReporterNodeProxy {
+  reporter_key: String,
+  node_instance: Arc<NodeInstance>,
+}
+
+impl ReporterNodeProxy {
+  pub async fn new(
+    node_instance: Arc<NodeInstance>,
+    specifier: &str,
+    config_path: &Path,
+  ) -> Result<Self, PluginLoadError> {
+    let req = LoadReporterRequest {
+      specifier: specifier.to_string(),
+      config_path: config_path.to_path_buf(),
+    };
+
+    let responses = match node_instance
+      .send_all::<_, Option<LoadReporterResponse>>("load_reporter", &req)
+      .await
+    {
+      Ok(responses) => responses,
+      Err(NodeError::NoResponse) => {
+        return Err(PluginLoadError::Unreachable {
+          specifier: specifier.to_string(),
+        })
+      }
+      Err(error) => {
+        return Err(PluginLoadError::Failed {
+          specifier: specifier.to_string(),
+          message: error.to_string(),
+        })
+      }
+    };
+
+    for response in responses.into_iter().flatten() {
+      if let Some(message) = response.error {
+        return Err(PluginLoadError::Failed {
+          specifier: specifier.to_string(),
+          message,
+        });
+      }
+    }
+
+    return Ok(Self {
+      reporter_key: specifier.to_string(),
+      node_instance,
+    });
+  }
+}
+
+impl Reporter for ReporterNodeProxy {
+  fn report(
+    &self,
+    event: &ReporterEvent,
+  ) {
+    let req = RunReporterRequest {
+      reporter_key: self.reporter_key.clone(),
+      event: event.clone(),
+    };
+
+    // Without workers there is no reporter to send the event to
+    self
+      .node_instance
+      .notify(REPORTER_WORKER, "run_reporter", &req)
+      .ok();
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LoadReporterRequest {
+  pub specifier: String,
+  pub config_path: PathBuf,
+}
+
+// Workers answer null once the reporter is loaded, or the error it threw
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct LoadReporterResponse {
+  pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunReporterRequest {
+  pub reporter_key: String,
+  pub event: ReporterEvent,
+}
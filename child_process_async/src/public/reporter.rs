@@ -0,0 +1,37 @@
+/*
+  This is the common interface for "Reporter" plugins
+
+  Reporters are told about the progress of a build. Events are sent
+  without waiting on the reporter so reporting never slows the
+  build down, a reporter receives them in the order they were sent
+*/
+use std::fmt::Debug;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+pub trait Reporter: Sync + Send + Debug {
+  fn report(
+    &self,
+    event: &ReporterEvent,
+  );
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReporterEvent {
+  BuildStart,
+  // A module is done, completed counts the modules done so far
+  Progress {
+    module: String,
+    completed: usize,
+  },
+  // Something went wrong that does not fail the build
+  Warning {
+    message: String,
+    module: Option<String>,
+  },
+  BuildEnd {
+    success: bool,
+  },
+}
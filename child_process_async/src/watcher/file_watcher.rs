@@ -0,0 +1,103 @@
+/*
+  Watches directories recursively (inotify on Linux) and translates
+  the raw notifications into FileEvents that the resolver layer uses
+  to invalidate cached results.
+
+  Renames are reported as a Delete of the old path followed by a
+  Create of the new one, so a file renamed into place is treated
+  the same as a newly created file.
+
+  Every subscriber receives every batch of events, until the
+  FileWatcher is dropped which ends their channels.
+*/
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use notify::event::ModifyKind;
+use notify::event::RenameMode;
+use notify::Event;
+use notify::EventKind;
+use notify::RecommendedWatcher;
+use notify::RecursiveMode;
+use notify::Watcher;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::public::FileEvent;
+
+pub struct FileWatcher {
+  subscribers: Arc<Mutex<Vec<UnboundedSender<Vec<FileEvent>>>>>,
+  // Watching stops when this is dropped
+  _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+  // Roots inside another root are already covered by it
+  pub fn new(roots: &[PathBuf]) -> notify::Result<Self> {
+    let subscribers = Arc::new(Mutex::new(Vec::<UnboundedSender<Vec<FileEvent>>>::new()));
+    let subscribers_thread = subscribers.clone();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+      let Ok(event) = event else {
+        return;
+      };
+      let events = to_file_events(event);
+      if events.is_empty() {
+        return;
+      }
+      // Drop subscribers that have gone away
+      subscribers_thread
+        .lock()
+        .unwrap()
+        .retain(|tx| tx.send(events.clone()).is_ok());
+    })?;
+
+    for root in roots {
+      let covered = roots
+        .iter()
+        .any(|other| other != root && root.starts_with(other));
+      if !covered {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+      }
+    }
+
+    return Ok(Self {
+      subscribers,
+      _watcher: watcher,
+    });
+  }
+
+  pub fn subscribe(&self) -> UnboundedReceiver<Vec<FileEvent>> {
+    let (tx, rx) = unbounded_channel::<Vec<FileEvent>>();
+    self.subscribers.lock().unwrap().push(tx);
+    return rx;
+  }
+}
+
+fn to_file_events(event: Event) -> Vec<FileEvent> {
+  let mut paths = event.paths.into_iter();
+
+  match event.kind {
+    EventKind::Create(_) => paths.map(FileEvent::Create).collect(),
+    EventKind::Remove(_) => paths.map(FileEvent::Delete).collect(),
+    EventKind::Modify(ModifyKind::Name(RenameMode::From)) => paths.map(FileEvent::Delete).collect(),
+    EventKind::Modify(ModifyKind::Name(RenameMode::To)) => paths.map(FileEvent::Create).collect(),
+    EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+      let (Some(from), Some(to)) = (paths.next(), paths.next()) else {
+        return vec![];
+      };
+      vec![FileEvent::Delete(from), FileEvent::Create(to)]
+    }
+    // The backend could not tell which side of the rename this is
+    EventKind::Modify(ModifyKind::Name(_)) => paths
+      .map(|path| match path.exists() {
+        true => FileEvent::Create(path),
+        false => FileEvent::Delete(path),
+      })
+      .collect(),
+    EventKind::Modify(_) => paths.map(FileEvent::Change).collect(),
+    _ => vec![],
+  }
+}
@@ -0,0 +1,3 @@
+mod file_watcher;
+
+pub use crate::watcher::file_watcher::*;
@@ -0,0 +1,4 @@
+export function render(routes, lazy) {
+  console.log(routes)
+  lazy()
+}
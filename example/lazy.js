@@ -0,0 +1,3 @@
+const { render } = require('./app.js')
+
+module.exports = render
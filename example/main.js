@@ -0,0 +1,7 @@
+import { render } from './app.js'
+import routes from 'virtual:routes'
+
+// Loaded on demand
+const lazy = () => import('./lazy.js')
+
+render(routes, lazy)
@@ -1,11 +1,52 @@
-const path = require('node:path');
-const { Worker } = require('node:worker_threads');
-const native = require('../index.node')
+const path = require('node:path')
+const { ResolverHost } = require('./resolver_host.js')
 
-const WORKERS = process.argv[2] ? parseInt(process.argv[2], 10) : 4;
+const WORKERS = process.argv[2] ? parseInt(process.argv[2], 10) : undefined;
+const CONCURRENT = process.argv.includes('--concurrent');
+// Requests each worker may be handling at once, e.g. --max-in-flight=16
+const MAX_IN_FLIGHT = parseInt(process.argv.find(arg => arg.startsWith('--max-in-flight='))?.split('=')[1] ?? '16', 10);
 
-for (let i = 0; i < WORKERS; i++) {
-  new Worker(path.join(__dirname, 'worker.js'))
+async function main() {
+  const host = new ResolverHost({
+    workers: WORKERS,
+    concurrent: CONCURRENT,
+    max_in_flight: MAX_IN_FLIGHT,
+  })
+
+  // Mimic running resolvers
+  try {
+    console.log('resolved:', await host.resolve(process.cwd(), 'hi'))
+  } catch (error) {
+    console.log('error:', error.message)
+  }
+
+  // Mimic running transformers
+  try {
+    console.log('transformed:', await host.transform('index.js', "import hi from 'hi';\n"))
+  } catch (error) {
+    console.log('error:', error.message)
+  }
+
+  // Mimic loading a virtual module
+  try {
+    const { result } = await host.resolve(process.cwd(), 'virtual:routes')
+    console.log('loaded:', await host.load(result.resolution))
+  } catch (error) {
+    console.log('error:', error.message)
+  }
+
+  // Mimic a build, the reporter plugin prints its progress
+  const results = await host.resolveMany([
+    [process.cwd(), 'hi'],
+    [process.cwd(), 'virtual:routes'],
+  ])
+  console.log('built:', results.length, 'modules')
+
+  // Mimic crawling the example project
+  const graph = await host.buildGraph([path.join(__dirname, '../../example/main.js')])
+  console.log('graph:', graph.nodes.length, 'modules,', graph.errors.length, 'errors')
+
+  await host.close()
 }
 
-native.register_main(WORKERS)
+main()
@@ -0,0 +1,52 @@
+const native = require('../index.node')
+const { spawn_worker } = require('./spawn_worker.js')
+
+let farm_count = 0
+
+/*
+  Runs the Rust resolver pipeline without blocking the main thread,
+  every method returns a Promise. Call close() when done so the
+  workers can exit. Plugins are read from the closest mach.config.json,
+  as is the worker count when none is passed
+*/
+class ResolverHost {
+  #host
+
+  constructor({ workers, concurrent = false, max_in_flight = 16 } = {}) {
+    // Workers register with the farm that has the same id, Rust
+    // starts them and restarts any that exit
+    const farm_id = `farm-${process.pid}-${farm_count++}`
+    this.#host = native.host_new(farm_id, workers ?? null, concurrent, max_in_flight, spawn_worker)
+  }
+
+  resolve(from_path, specifier) {
+    return native.host_resolve(this.#host, from_path, specifier)
+  }
+
+  // requests: [from_path, specifier][], failed items are { error }
+  resolveMany(requests) {
+    return native.host_resolve_many(this.#host, requests)
+  }
+
+  // Runs the code through the transformer plugin
+  transform(file_path, code) {
+    return native.host_transform(this.#host, file_path, code)
+  }
+
+  // Loads the module a resolution refers to, including virtual
+  // modules, resolves to null when no loader claims it
+  load(resolution) {
+    return native.host_load(this.#host, resolution)
+  }
+
+  // Crawls the imports from the entry files, resolves to the module graph
+  buildGraph(entries) {
+    return native.host_build_graph(this.#host, entries)
+  }
+
+  close() {
+    return native.host_close(this.#host)
+  }
+}
+
+module.exports = { ResolverHost }
@@ -0,0 +1,17 @@
+const path = require('node:path');
+const { Worker } = require('node:worker_threads');
+
+/*
+  Called from Rust to start a worker for a slot in a farm, Rust
+  decides whether to start another one when on_exit is called
+*/
+function spawn_worker(farm_id, slot, on_exit) {
+  const worker = new Worker(path.join(__dirname, 'worker.js'), { workerData: { farm_id, slot } })
+
+  // "error" is emitted before "exit" when the worker throws
+  let error = null
+  worker.on('error', (e) => { error = e })
+  worker.on('exit', (code) => on_exit(code, error ? String(error.stack ?? error) : null))
+}
+
+module.exports = { spawn_worker }
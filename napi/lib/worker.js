@@ -1,12 +1,5 @@
+const { workerData } = require('node:worker_threads');
 const native = require('../index.node')
 
-const resolvers = {}
-
-function load_resolver({ specifier }) {
-  resolvers[specifier] = require(specifier)
-}
-
-globalThis.load_resolver = load_resolver
-globalThis.resolvers = resolvers
-
-native.register_worker()
+// Plugins are required from the config file by a require made with this one
+native.register_worker(workerData.farm_id, workerData.slot, require)
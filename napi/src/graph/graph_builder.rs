@@ -0,0 +1,278 @@
+/*
+  Builds the module graph by crawling from the entries. Each module
+  is read from disk and scanned for imports, which are resolved
+  through the resolver pipeline
+
+  The crawl goes in waves, the imports of every module found in the
+  previous wave are resolved together on a pool of threads. Virtual
+  modules, externals and builtins are not crawled any further
+*/
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
+
+use crate::pipeline::PipelineError;
+use crate::pipeline::PipelineResult;
+use crate::pipeline::ResolverPipeline;
+use crate::public::Dependency;
+use crate::public::Resolution;
+use crate::public::ResolveOptions;
+use crate::public::ResolvedId;
+
+use super::scan_imports;
+use super::GraphEdge;
+use super::GraphError;
+use super::GraphNode;
+use super::ModuleGraph;
+
+pub struct GraphBuilder<'a> {
+  resolvers: &'a ResolverPipeline,
+  options: ResolveOptions,
+  threads: usize,
+}
+
+// An import found in the module at index "from"
+struct ImportRequest {
+  from: usize,
+  from_dir: PathBuf,
+  dependency: Dependency,
+}
+
+impl<'a> GraphBuilder<'a> {
+  pub fn new(
+    resolvers: &'a ResolverPipeline,
+    options: ResolveOptions,
+  ) -> Self {
+    Self {
+      resolvers,
+      options,
+      threads: thread::available_parallelism().map_or(4, |threads| threads.get()),
+    }
+  }
+
+  pub fn build(
+    &self,
+    entries: &[PathBuf],
+  ) -> ModuleGraph {
+    let mut graph = ModuleGraph::default();
+    let mut indexes = HashMap::<ResolvedId, usize>::new();
+    let mut wave = vec![];
+
+    for entry in entries {
+      if let (index, true) = add_node(&mut graph, &mut indexes, ResolvedId::Path(entry.clone())) {
+        wave.push(index);
+      }
+    }
+
+    while !wave.is_empty() {
+      let requests = self.scan(&mut graph, &wave);
+      let results = self.resolve_all(&requests);
+      wave.clear();
+
+      for (request, result) in requests.into_iter().zip(results) {
+        let specifier = request.dependency.specifier;
+
+        let resolution = match result {
+          Ok(result) => result.result.resolution,
+          Err(error) => {
+            graph.errors.push(GraphError {
+              from: request.from,
+              specifier: Some(specifier),
+              message: error.to_string(),
+            });
+            continue;
+          }
+        };
+
+        let to = match resolution.resolved_id() {
+          Some(id) => {
+            let (index, added) = add_node(&mut graph, &mut indexes, id);
+            if added {
+              wave.push(index);
+            }
+            Some(index)
+          }
+          None if resolution == Resolution::Unresolved => {
+            graph.errors.push(GraphError {
+              from: request.from,
+              message: format!("No resolver could resolve \"{}\"", specifier),
+              specifier: Some(specifier),
+            });
+            continue;
+          }
+          None => None,
+        };
+
+        graph.edges.push(GraphEdge {
+          from: request.from,
+          to,
+          specifier,
+          kind: request.dependency.kind,
+        });
+      }
+    }
+
+    return graph;
+  }
+
+  // Reads the modules and collects the imports they contain
+  fn scan(
+    &self,
+    graph: &mut ModuleGraph,
+    wave: &[usize],
+  ) -> Vec<ImportRequest> {
+    let mut requests = vec![];
+
+    for &index in wave {
+      let ResolvedId::Path(file_path) = &graph.nodes[index].id else {
+        continue;
+      };
+
+      let code = match fs::read_to_string(file_path) {
+        Ok(code) => code,
+        Err(error) => {
+          graph.errors.push(GraphError {
+            from: index,
+            specifier: None,
+            message: format!("Unable to read \"{}\": {}", file_path.display(), error),
+          });
+          continue;
+        }
+      };
+
+      let from_dir = file_path.parent().unwrap_or(Path::new("/")).to_path_buf();
+      for dependency in scan_imports(&code) {
+        requests.push(ImportRequest {
+          from: index,
+          from_dir: from_dir.clone(),
+          dependency,
+        });
+      }
+    }
+
+    return requests;
+  }
+
+  // Resolves on several threads, the results are in the same order
+  // as the requests
+  fn resolve_all(
+    &self,
+    requests: &[ImportRequest],
+  ) -> Vec<Result<PipelineResult, PipelineError>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; requests.len()]);
+
+    thread::scope(|scope| {
+      for _ in 0..self.threads.min(requests.len()) {
+        scope.spawn(|| loop {
+          let index = next.fetch_add(1, Ordering::Relaxed);
+          let Some(request) = requests.get(index) else {
+            break;
+          };
+
+          let options = ResolveOptions {
+            kind: request.dependency.kind,
+            ..self.options.clone()
+          };
+          let result = self
+            .resolvers
+            .resolve(&request.from_dir, &request.dependency.specifier, &options);
+          results.lock().unwrap()[index] = Some(result);
+        });
+      }
+    });
+
+    return results.into_inner().unwrap().into_iter().flatten().collect();
+  }
+}
+
+// Returns the index of the node and whether it was just added
+fn add_node(
+  graph: &mut ModuleGraph,
+  indexes: &mut HashMap<ResolvedId, usize>,
+  id: ResolvedId,
+) -> (usize, bool) {
+  if let Some(index) = indexes.get(&id) {
+    return (*index, false);
+  }
+  let index = graph.nodes.len();
+  indexes.insert(id.clone(), index);
+  graph.nodes.push(GraphNode { id });
+  return (index, true);
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env;
+  use std::fs;
+  use std::process;
+
+  use super::*;
+  use crate::public::ResolveError;
+  use crate::public::ResolveResult;
+  use crate::public::Resolver;
+
+  // Resolves every specifier relative to the importing module
+  #[derive(Debug)]
+  struct RelativeResolver {}
+
+  impl Resolver for RelativeResolver {
+    fn resolve(
+      &self,
+      from_path: &Path,
+      specifier: &str,
+      _options: &ResolveOptions,
+    ) -> Result<ResolveResult, ResolveError> {
+      return Ok(ResolveResult::path(from_path.join(specifier)));
+    }
+  }
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("mach-graph-{}-{}", process::id(), name));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    return dir;
+  }
+
+  fn build(entry: &Path) -> ModuleGraph {
+    let mut resolvers = ResolverPipeline::new(false);
+    resolvers.add("relative", 0, RelativeResolver {});
+    return GraphBuilder::new(&resolvers, ResolveOptions::default()).build(&[entry.to_path_buf()]);
+  }
+
+  #[test]
+  fn visits_each_module_of_a_cycle_once() {
+    let dir = temp_dir("cycle");
+    fs::write(dir.join("a.js"), "import './b.js'").unwrap();
+    fs::write(dir.join("b.js"), "import './c.js'\nrequire('./a.js')").unwrap();
+    fs::write(dir.join("c.js"), "import('./a.js')").unwrap();
+
+    let graph = build(&dir.join("a.js"));
+    assert!(graph.errors.is_empty());
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(graph.edges.len(), 4);
+    let to_entry = graph.edges.iter().filter(|edge| edge.to == Some(0));
+    assert_eq!(to_entry.count(), 2);
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn reports_modules_that_cannot_be_read() {
+    let dir = temp_dir("missing");
+    fs::write(dir.join("a.js"), "import './missing.js'").unwrap();
+
+    let graph = build(&dir.join("a.js"));
+    assert_eq!(graph.nodes.len(), 2);
+    assert_eq!(graph.errors.len(), 1);
+    assert_eq!(graph.errors[0].from, 1);
+    assert_eq!(graph.errors[0].specifier, None);
+
+    fs::remove_dir_all(&dir).ok();
+  }
+}
@@ -0,0 +1,159 @@
+/*
+  Byte payloads (file contents, source maps) that go to and from the
+  Node workers
+
+  Rust → JS
+    Bytes::into_js hands the Vec to JS as an external Buffer without
+    copying it, JS owns the memory from then on and frees it once the
+    Buffer is garbage collected
+  JS → Rust
+    A returned Buffer (or string) is copied once, on the JS thread.
+    Reading it in place off the JS thread is not sound under neon 0.10,
+    the plugin can still write to or detach the Buffer, so Bytes is
+    always plain memory owned by Rust and can be read from any thread
+
+  Serializing Bytes through JsSerializer copies them into a new Buffer,
+  other serde formats see Bytes as plain bytes
+*/
+use std::ops::Deref;
+
+use neon::prelude::*;
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Bytes(Vec<u8>);
+
+impl Bytes {
+  pub fn into_vec(self) -> Vec<u8> {
+    return self.0;
+  }
+
+  // Gives the memory to JS as a Buffer, without a copy
+  pub fn into_js<'a>(
+    self,
+    cx: &mut impl Context<'a>,
+  ) -> Handle<'a, JsBuffer> {
+    return JsBuffer::external(cx, self.0);
+  }
+}
+
+impl From<Vec<u8>> for Bytes {
+  fn from(data: Vec<u8>) -> Self {
+    return Self(data);
+  }
+}
+
+impl From<String> for Bytes {
+  fn from(data: String) -> Self {
+    return Self(data.into_bytes());
+  }
+}
+
+impl Deref for Bytes {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    return &self.0;
+  }
+}
+
+impl std::fmt::Debug for Bytes {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    write!(f, "Bytes({} bytes)", self.0.len())
+  }
+}
+
+impl Serialize for Bytes {
+  fn serialize<S: Serializer>(
+    &self,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(&self.0)
+  }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer.deserialize_byte_buf(BytesVisitor)
+  }
+}
+
+struct BytesVisitor;
+
+impl<'de> de::Visitor<'de> for BytesVisitor {
+  type Value = Bytes;
+
+  fn expecting(
+    &self,
+    f: &mut std::fmt::Formatter,
+  ) -> std::fmt::Result {
+    write!(f, "a Buffer or a string")
+  }
+
+  fn visit_bytes<E: de::Error>(
+    self,
+    v: &[u8],
+  ) -> Result<Bytes, E> {
+    Ok(Bytes::from(v.to_vec()))
+  }
+
+  fn visit_byte_buf<E: de::Error>(
+    self,
+    v: Vec<u8>,
+  ) -> Result<Bytes, E> {
+    Ok(Bytes::from(v))
+  }
+
+  fn visit_str<E: de::Error>(
+    self,
+    v: &str,
+  ) -> Result<Bytes, E> {
+    Ok(Bytes::from(v.to_string()))
+  }
+
+  fn visit_string<E: de::Error>(
+    self,
+    v: String,
+  ) -> Result<Bytes, E> {
+    Ok(Bytes::from(v))
+  }
+
+  fn visit_seq<A: de::SeqAccess<'de>>(
+    self,
+    mut seq: A,
+  ) -> Result<Bytes, A::Error> {
+    let mut data = Vec::<u8>::with_capacity(seq.size_hint().unwrap_or(0));
+    while let Some(byte) = seq.next_element::<u8>()? {
+      data.push(byte);
+    }
+    return Ok(Bytes::from(data));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn strings_and_byte_arrays_both_read_as_bytes() {
+    let from_string = serde_json::from_str::<Bytes>("\"export {}\"").unwrap();
+    let from_array = serde_json::from_str::<Bytes>("[104, 105]").unwrap();
+    assert_eq!(from_string.into_vec(), b"export {}".to_vec());
+    assert_eq!(&*from_array, b"hi");
+  }
+
+  #[test]
+  fn bytes_round_trip_through_other_formats() {
+    let bytes = Bytes::from(vec![0, 159, 146, 150]);
+    let json = serde_json::to_string(&bytes).unwrap();
+    assert_eq!(json, "[0,159,146,150]");
+    assert_eq!(serde_json::from_str::<Bytes>(&json).unwrap(), bytes);
+  }
+}
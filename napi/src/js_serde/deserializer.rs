@@ -0,0 +1,313 @@
+/*
+  Converts JS values into any Deserialize type, the reverse of
+  JsSerializer:
+    - objects become structs or maps, arrays sequences or tuples
+    - null and undefined become None or ()
+    - properties that are undefined are treated as missing
+    - enums are read from "Variant" or { Variant: value }
+    - Buffers and ArrayBuffers become bytes
+
+  Mismatches are reported with the path to the offending value
+*/
+use std::vec::IntoIter;
+
+use neon::prelude::*;
+use neon::types::buffer::TypedArray;
+use serde::de;
+use serde::de::value::StringDeserializer;
+use serde::de::DeserializeOwned;
+use serde::de::DeserializeSeed;
+use serde::de::IntoDeserializer;
+use serde::de::Visitor;
+use serde::forward_to_deserialize_any;
+
+use super::JsSerdeError;
+
+// Integers above this can't be represented exactly by a JS number
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+pub fn from_js<'a, C: Context<'a>, T: DeserializeOwned>(
+  cx: &mut C,
+  value: Handle<'a, JsValue>,
+) -> NeonResult<T> {
+  match T::deserialize(JsDeserializer::new(cx, value)) {
+    Ok(value) => Ok(value),
+    Err(error) => error.throw(cx),
+  }
+}
+
+pub struct JsDeserializer<'a, 'b, C: Context<'a>> {
+  cx: &'b mut C,
+  value: Handle<'a, JsValue>,
+}
+
+impl<'a, 'b, C: Context<'a>> JsDeserializer<'a, 'b, C> {
+  pub fn new(
+    cx: &'b mut C,
+    value: Handle<'a, JsValue>,
+  ) -> Self {
+    Self { cx, value }
+  }
+
+  fn is_nullish(&mut self) -> bool {
+    self.value.is_a::<JsNull, _>(self.cx) || self.value.is_a::<JsUndefined, _>(self.cx)
+  }
+}
+
+impl<'de, 'a, 'b, C: Context<'a>> de::Deserializer<'de> for JsDeserializer<'a, 'b, C> {
+  type Error = JsSerdeError;
+
+  fn deserialize_any<V: Visitor<'de>>(
+    mut self,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    if self.is_nullish() {
+      return visitor.visit_unit();
+    }
+
+    let cx = self.cx;
+    let value = self.value;
+
+    if let Ok(value) = value.downcast::<JsBoolean, _>(cx) {
+      return visitor.visit_bool(value.value(cx));
+    }
+    if let Ok(value) = value.downcast::<JsNumber, _>(cx) {
+      let value = value.value(cx);
+      if value.fract() != 0.0 || value.abs() > MAX_SAFE_INTEGER {
+        return visitor.visit_f64(value);
+      }
+      if value < 0.0 {
+        return visitor.visit_i64(value as i64);
+      }
+      return visitor.visit_u64(value as u64);
+    }
+    if let Ok(value) = value.downcast::<JsString, _>(cx) {
+      return visitor.visit_string(value.value(cx));
+    }
+    if let Ok(value) = value.downcast::<JsBuffer, _>(cx) {
+      return visitor.visit_byte_buf(value.as_slice(cx).to_vec());
+    }
+    if let Ok(value) = value.downcast::<JsArrayBuffer, _>(cx) {
+      return visitor.visit_byte_buf(value.as_slice(cx).to_vec());
+    }
+    if value.is_a::<JsFunction, _>(cx) {
+      return Err(de::Error::custom("functions can't be converted"));
+    }
+    if let Ok(value) = value.downcast::<JsArray, _>(cx) {
+      let items = value.to_vec(cx)?;
+      return visitor.visit_seq(JsSeqAccess {
+        cx,
+        items: items.into_iter(),
+        index: 0,
+      });
+    }
+    if let Ok(value) = value.downcast::<JsObject, _>(cx) {
+      let keys = value.get_own_property_names(cx)?.to_vec(cx)?;
+      return visitor.visit_map(JsMapAccess {
+        cx,
+        object: value,
+        keys: keys.into_iter(),
+        next_value: None,
+      });
+    }
+
+    return Err(de::Error::custom("unsupported JavaScript value"));
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(
+    mut self,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    if self.is_nullish() {
+      return visitor.visit_none();
+    }
+    return visitor.visit_some(self);
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    let cx = self.cx;
+
+    // Unit variants, "Variant"
+    if let Ok(value) = self.value.downcast::<JsString, _>(cx) {
+      let variant: StringDeserializer<JsSerdeError> = value.value(cx).into_deserializer();
+      return visitor.visit_enum(variant);
+    }
+
+    // { Variant: value }
+    let Ok(object) = self.value.downcast::<JsObject, _>(cx) else {
+      return Err(de::Error::custom("expected a string or an object with a single key"));
+    };
+    let keys = object.get_own_property_names(cx)?.to_vec(cx)?;
+    let [key] = keys.as_slice() else {
+      return Err(de::Error::custom("expected a string or an object with a single key"));
+    };
+    let key = key.to_string(cx)?.value(cx);
+    let value = object.get_value(cx, key.as_str())?;
+
+    return visitor.visit_enum(JsEnumAccess {
+      cx,
+      variant: key,
+      value,
+    });
+  }
+
+  forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+    identifier ignored_any
+  }
+}
+
+struct JsSeqAccess<'a, 'b, C: Context<'a>> {
+  cx: &'b mut C,
+  items: IntoIter<Handle<'a, JsValue>>,
+  index: usize,
+}
+
+impl<'de, 'a, 'b, C: Context<'a>> de::SeqAccess<'de> for JsSeqAccess<'a, 'b, C> {
+  type Error = JsSerdeError;
+
+  fn next_element_seed<T: DeserializeSeed<'de>>(
+    &mut self,
+    seed: T,
+  ) -> Result<Option<T::Value>, Self::Error> {
+    let Some(item) = self.items.next() else {
+      return Ok(None);
+    };
+    let index = self.index;
+    self.index += 1;
+
+    return seed
+      .deserialize(JsDeserializer::new(&mut *self.cx, item))
+      .map(Some)
+      .map_err(|error| error.at(&format!("[{}]", index)));
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.items.len())
+  }
+}
+
+struct JsMapAccess<'a, 'b, C: Context<'a>> {
+  cx: &'b mut C,
+  object: Handle<'a, JsObject>,
+  keys: IntoIter<Handle<'a, JsValue>>,
+  // The value for the key returned last
+  next_value: Option<(String, Handle<'a, JsValue>)>,
+}
+
+impl<'de, 'a, 'b, C: Context<'a>> de::MapAccess<'de> for JsMapAccess<'a, 'b, C> {
+  type Error = JsSerdeError;
+
+  fn next_key_seed<K: DeserializeSeed<'de>>(
+    &mut self,
+    seed: K,
+  ) -> Result<Option<K::Value>, Self::Error> {
+    for key in self.keys.by_ref() {
+      let key = key.to_string(self.cx)?.value(self.cx);
+      let value = self.object.get_value(self.cx, key.as_str())?;
+      if value.is_a::<JsUndefined, _>(self.cx) {
+        continue;
+      }
+
+      self.next_value = Some((key.clone(), value));
+      let key: StringDeserializer<JsSerdeError> = key.into_deserializer();
+      return seed.deserialize(key).map(Some);
+    }
+    return Ok(None);
+  }
+
+  fn next_value_seed<V: DeserializeSeed<'de>>(
+    &mut self,
+    seed: V,
+  ) -> Result<V::Value, Self::Error> {
+    let Some((key, value)) = self.next_value.take() else {
+      return Err(de::Error::custom("value requested before key"));
+    };
+    return seed
+      .deserialize(JsDeserializer::new(&mut *self.cx, value))
+      .map_err(|error| error.at(&key));
+  }
+}
+
+struct JsEnumAccess<'a, 'b, C: Context<'a>> {
+  cx: &'b mut C,
+  variant: String,
+  value: Handle<'a, JsValue>,
+}
+
+impl<'de, 'a, 'b, C: Context<'a>> de::EnumAccess<'de> for JsEnumAccess<'a, 'b, C> {
+  type Error = JsSerdeError;
+  type Variant = JsVariantAccess<'a, 'b, C>;
+
+  fn variant_seed<V: DeserializeSeed<'de>>(
+    self,
+    seed: V,
+  ) -> Result<(V::Value, Self::Variant), Self::Error> {
+    let variant: StringDeserializer<JsSerdeError> = self.variant.clone().into_deserializer();
+    let variant = seed.deserialize(variant)?;
+
+    return Ok((
+      variant,
+      JsVariantAccess {
+        cx: self.cx,
+        variant: self.variant,
+        value: self.value,
+      },
+    ));
+  }
+}
+
+struct JsVariantAccess<'a, 'b, C: Context<'a>> {
+  cx: &'b mut C,
+  variant: String,
+  value: Handle<'a, JsValue>,
+}
+
+impl<'de, 'a, 'b, C: Context<'a>> de::VariantAccess<'de> for JsVariantAccess<'a, 'b, C> {
+  type Error = JsSerdeError;
+
+  fn unit_variant(self) -> Result<(), Self::Error> {
+    Ok(())
+  }
+
+  fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+    self,
+    seed: T,
+  ) -> Result<T::Value, Self::Error> {
+    seed
+      .deserialize(JsDeserializer::new(self.cx, self.value))
+      .map_err(|error| error.at(&self.variant))
+  }
+
+  fn tuple_variant<V: Visitor<'de>>(
+    self,
+    _len: usize,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    de::Deserializer::deserialize_seq(JsDeserializer::new(self.cx, self.value), visitor)
+      .map_err(|error| error.at(&self.variant))
+  }
+
+  fn struct_variant<V: Visitor<'de>>(
+    self,
+    _fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    de::Deserializer::deserialize_map(JsDeserializer::new(self.cx, self.value), visitor)
+      .map_err(|error| error.at(&self.variant))
+  }
+}
@@ -0,0 +1,125 @@
+/*
+  Errors raised while converting between Rust types and JS values.
+
+  When a conversion fails inside a field or an array item the error
+  carries the path to it, "options.conditions[1]: invalid type..."
+*/
+use std::fmt::Display;
+
+use neon::prelude::*;
+use neon::result::Throw;
+
+#[derive(Debug)]
+pub enum JsSerdeError {
+  Invalid {
+    path: String,
+    message: String,
+  },
+  // A JavaScript exception is pending and should be rethrown as-is
+  Throw(Throw),
+}
+
+impl JsSerdeError {
+  // Prefixes the path with the field name or "[index]" it happened in
+  pub fn at(
+    self,
+    segment: &str,
+  ) -> Self {
+    let JsSerdeError::Invalid { path, message } = self else {
+      return self;
+    };
+
+    let path = if path.is_empty() {
+      segment.to_string()
+    } else if path.starts_with('[') {
+      format!("{}{}", segment, path)
+    } else {
+      format!("{}.{}", segment, path)
+    };
+
+    return JsSerdeError::Invalid { path, message };
+  }
+
+  // Throws the error as a JS TypeError
+  pub fn throw<'a, C: Context<'a>, T>(
+    self,
+    cx: &mut C,
+  ) -> NeonResult<T> {
+    match self {
+      JsSerdeError::Invalid { .. } => cx.throw_type_error(self.to_string()),
+      JsSerdeError::Throw(throw) => Err(throw),
+    }
+  }
+}
+
+impl From<Throw> for JsSerdeError {
+  fn from(throw: Throw) -> Self {
+    JsSerdeError::Throw(throw)
+  }
+}
+
+impl Display for JsSerdeError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      JsSerdeError::Invalid { path, message } if path.is_empty() => write!(f, "{}", message),
+      JsSerdeError::Invalid { path, message } => write!(f, "{}: {}", path, message),
+      JsSerdeError::Throw(_) => write!(f, "A JavaScript exception was thrown"),
+    }
+  }
+}
+
+impl std::error::Error for JsSerdeError {}
+
+impl serde::ser::Error for JsSerdeError {
+  fn custom<T: Display>(msg: T) -> Self {
+    JsSerdeError::Invalid {
+      path: String::new(),
+      message: msg.to_string(),
+    }
+  }
+}
+
+impl serde::de::Error for JsSerdeError {
+  fn custom<T: Display>(msg: T) -> Self {
+    JsSerdeError::Invalid {
+      path: String::new(),
+      message: msg.to_string(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde::de::Error;
+
+  use super::*;
+
+  #[test]
+  fn paths_are_built_from_the_innermost_segment_out() {
+    let error = JsSerdeError::custom("invalid type: number, expected a string")
+      .at("[1]")
+      .at("conditions")
+      .at("options");
+    assert_eq!(
+      error.to_string(),
+      "options.conditions[1]: invalid type: number, expected a string"
+    );
+  }
+
+  #[test]
+  fn nested_arrays_join_without_dots() {
+    let error = JsSerdeError::custom("missing field `id`")
+      .at("[0]")
+      .at("[2]")
+      .at("requests");
+    assert_eq!(error.to_string(), "requests[2][0]: missing field `id`");
+  }
+
+  #[test]
+  fn errors_without_a_path_are_just_the_message() {
+    assert_eq!(JsSerdeError::custom("oops").to_string(), "oops");
+  }
+}
@@ -0,0 +1,9 @@
+mod bytes;
+mod deserializer;
+mod error;
+mod serializer;
+
+pub use crate::js_serde::bytes::*;
+pub use crate::js_serde::deserializer::*;
+pub use crate::js_serde::error::*;
+pub use crate::js_serde::serializer::*;
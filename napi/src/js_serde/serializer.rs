@@ -0,0 +1,704 @@
+/*
+  Converts any Serialize type into a JS value, following the same
+  conventions as serde_json:
+    - structs and maps become objects, sequences and tuples arrays
+    - None and () become null
+    - enums are externally tagged, "Variant" or { Variant: value }
+    - bytes become a Buffer
+*/
+use std::marker::PhantomData;
+
+use neon::prelude::*;
+use neon::types::buffer::TypedArray;
+use serde::ser;
+use serde::Serialize;
+
+use super::JsSerdeError;
+
+pub fn to_js<'a, C: Context<'a>, T: Serialize + ?Sized>(
+  cx: &mut C,
+  value: &T,
+) -> JsResult<'a, JsValue> {
+  match value.serialize(JsSerializer::new(cx)) {
+    Ok(value) => Ok(value),
+    Err(error) => error.throw(cx),
+  }
+}
+
+pub struct JsSerializer<'a, 'b, C: Context<'a>> {
+  cx: &'b mut C,
+  _lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a, 'b, C: Context<'a>> JsSerializer<'a, 'b, C> {
+  pub fn new(cx: &'b mut C) -> Self {
+    Self {
+      cx,
+      _lifetime: PhantomData,
+    }
+  }
+}
+
+// { variant: value } for non-unit enum variants
+fn tagged<'a, C: Context<'a>>(
+  cx: &mut C,
+  variant: &str,
+  value: Handle<'a, JsValue>,
+) -> Result<Handle<'a, JsValue>, JsSerdeError> {
+  let js_obj = cx.empty_object();
+  js_obj.set(cx, variant, value)?;
+  return Ok(js_obj.upcast());
+}
+
+impl<'a, 'b, C: Context<'a>> ser::Serializer for JsSerializer<'a, 'b, C> {
+  type Ok = Handle<'a, JsValue>;
+  type Error = JsSerdeError;
+
+  type SerializeSeq = ArraySerializer<'a, 'b, C>;
+  type SerializeTuple = ArraySerializer<'a, 'b, C>;
+  type SerializeTupleStruct = ArraySerializer<'a, 'b, C>;
+  type SerializeTupleVariant = ArraySerializer<'a, 'b, C>;
+  type SerializeMap = ObjectSerializer<'a, 'b, C>;
+  type SerializeStruct = ObjectSerializer<'a, 'b, C>;
+  type SerializeStructVariant = ObjectSerializer<'a, 'b, C>;
+
+  fn serialize_bool(
+    self,
+    v: bool,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.boolean(v).upcast())
+  }
+
+  fn serialize_i8(
+    self,
+    v: i8,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v).upcast())
+  }
+
+  fn serialize_i16(
+    self,
+    v: i16,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v).upcast())
+  }
+
+  fn serialize_i32(
+    self,
+    v: i32,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v).upcast())
+  }
+
+  // JS numbers lose precision above 2^53
+  fn serialize_i64(
+    self,
+    v: i64,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v as f64).upcast())
+  }
+
+  fn serialize_u8(
+    self,
+    v: u8,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v).upcast())
+  }
+
+  fn serialize_u16(
+    self,
+    v: u16,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v).upcast())
+  }
+
+  fn serialize_u32(
+    self,
+    v: u32,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v).upcast())
+  }
+
+  fn serialize_u64(
+    self,
+    v: u64,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v as f64).upcast())
+  }
+
+  fn serialize_f32(
+    self,
+    v: f32,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v).upcast())
+  }
+
+  fn serialize_f64(
+    self,
+    v: f64,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.number(v).upcast())
+  }
+
+  fn serialize_char(
+    self,
+    v: char,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.string(v.to_string()).upcast())
+  }
+
+  fn serialize_str(
+    self,
+    v: &str,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.string(v).upcast())
+  }
+
+  fn serialize_bytes(
+    self,
+    v: &[u8],
+  ) -> Result<Self::Ok, Self::Error> {
+    let mut buffer = JsBuffer::new(self.cx, v.len())?;
+    buffer.as_mut_slice(self.cx).copy_from_slice(v);
+    Ok(buffer.upcast())
+  }
+
+  fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.null().upcast())
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(
+    self,
+    value: &T,
+  ) -> Result<Self::Ok, Self::Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.null().upcast())
+  }
+
+  fn serialize_unit_struct(
+    self,
+    _name: &'static str,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.null().upcast())
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+  ) -> Result<Self::Ok, Self::Error> {
+    Ok(self.cx.string(variant).upcast())
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    value: &T,
+  ) -> Result<Self::Ok, Self::Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    value: &T,
+  ) -> Result<Self::Ok, Self::Error> {
+    let value = value.serialize(JsSerializer::new(&mut *self.cx))?;
+    tagged(self.cx, variant, value)
+  }
+
+  fn serialize_seq(
+    self,
+    _len: Option<usize>,
+  ) -> Result<Self::SerializeSeq, Self::Error> {
+    Ok(ArraySerializer::new(self.cx, None))
+  }
+
+  fn serialize_tuple(
+    self,
+    _len: usize,
+  ) -> Result<Self::SerializeTuple, Self::Error> {
+    Ok(ArraySerializer::new(self.cx, None))
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+    Ok(ArraySerializer::new(self.cx, None))
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+    Ok(ArraySerializer::new(self.cx, Some(variant)))
+  }
+
+  fn serialize_map(
+    self,
+    _len: Option<usize>,
+  ) -> Result<Self::SerializeMap, Self::Error> {
+    Ok(ObjectSerializer::new(self.cx, None))
+  }
+
+  fn serialize_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStruct, Self::Error> {
+    Ok(ObjectSerializer::new(self.cx, None))
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStructVariant, Self::Error> {
+    Ok(ObjectSerializer::new(self.cx, Some(variant)))
+  }
+}
+
+pub struct ArraySerializer<'a, 'b, C: Context<'a>> {
+  cx: &'b mut C,
+  array: Handle<'a, JsArray>,
+  len: u32,
+  // Set for tuple variants
+  variant: Option<&'static str>,
+}
+
+impl<'a, 'b, C: Context<'a>> ArraySerializer<'a, 'b, C> {
+  fn new(
+    cx: &'b mut C,
+    variant: Option<&'static str>,
+  ) -> Self {
+    let array = cx.empty_array();
+    Self {
+      cx,
+      array,
+      len: 0,
+      variant,
+    }
+  }
+
+  fn push<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), JsSerdeError> {
+    let value = value
+      .serialize(JsSerializer::new(&mut *self.cx))
+      .map_err(|error| error.at(&format!("[{}]", self.len)))?;
+    self.array.set(self.cx, self.len, value)?;
+    self.len += 1;
+    return Ok(());
+  }
+
+  fn finish(self) -> Result<Handle<'a, JsValue>, JsSerdeError> {
+    match self.variant {
+      Some(variant) => tagged(self.cx, variant, self.array.upcast()),
+      None => Ok(self.array.upcast()),
+    }
+  }
+}
+
+impl<'a, 'b, C: Context<'a>> ser::SerializeSeq for ArraySerializer<'a, 'b, C> {
+  type Ok = Handle<'a, JsValue>;
+  type Error = JsSerdeError;
+
+  fn serialize_element<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl<'a, 'b, C: Context<'a>> ser::SerializeTuple for ArraySerializer<'a, 'b, C> {
+  type Ok = Handle<'a, JsValue>;
+  type Error = JsSerdeError;
+
+  fn serialize_element<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl<'a, 'b, C: Context<'a>> ser::SerializeTupleStruct for ArraySerializer<'a, 'b, C> {
+  type Ok = Handle<'a, JsValue>;
+  type Error = JsSerdeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl<'a, 'b, C: Context<'a>> ser::SerializeTupleVariant for ArraySerializer<'a, 'b, C> {
+  type Ok = Handle<'a, JsValue>;
+  type Error = JsSerdeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    self.push(value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+pub struct ObjectSerializer<'a, 'b, C: Context<'a>> {
+  cx: &'b mut C,
+  object: Handle<'a, JsObject>,
+  // Map keys are serialized before their values
+  next_key: Option<String>,
+  // Set for struct variants
+  variant: Option<&'static str>,
+}
+
+impl<'a, 'b, C: Context<'a>> ObjectSerializer<'a, 'b, C> {
+  fn new(
+    cx: &'b mut C,
+    variant: Option<&'static str>,
+  ) -> Self {
+    let object = cx.empty_object();
+    Self {
+      cx,
+      object,
+      next_key: None,
+      variant,
+    }
+  }
+
+  fn set<T: Serialize + ?Sized>(
+    &mut self,
+    key: &str,
+    value: &T,
+  ) -> Result<(), JsSerdeError> {
+    let value = value
+      .serialize(JsSerializer::new(&mut *self.cx))
+      .map_err(|error| error.at(key))?;
+    self.object.set(self.cx, key, value)?;
+    return Ok(());
+  }
+
+  fn finish(self) -> Result<Handle<'a, JsValue>, JsSerdeError> {
+    match self.variant {
+      Some(variant) => tagged(self.cx, variant, self.object.upcast()),
+      None => Ok(self.object.upcast()),
+    }
+  }
+}
+
+impl<'a, 'b, C: Context<'a>> ser::SerializeMap for ObjectSerializer<'a, 'b, C> {
+  type Ok = Handle<'a, JsValue>;
+  type Error = JsSerdeError;
+
+  fn serialize_key<T: Serialize + ?Sized>(
+    &mut self,
+    key: &T,
+  ) -> Result<(), Self::Error> {
+    self.next_key = Some(key.serialize(KeySerializer)?);
+    return Ok(());
+  }
+
+  fn serialize_value<T: Serialize + ?Sized>(
+    &mut self,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    let key = self.next_key.take().unwrap_or_default();
+    self.set(&key, value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl<'a, 'b, C: Context<'a>> ser::SerializeStruct for ObjectSerializer<'a, 'b, C> {
+  type Ok = Handle<'a, JsValue>;
+  type Error = JsSerdeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    self.set(key, value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+impl<'a, 'b, C: Context<'a>> ser::SerializeStructVariant for ObjectSerializer<'a, 'b, C> {
+  type Ok = Handle<'a, JsValue>;
+  type Error = JsSerdeError;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), Self::Error> {
+    self.set(key, value)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.finish()
+  }
+}
+
+// Object keys are strings, numbers and unit variants are stringified
+struct KeySerializer;
+
+fn key_must_be_a_string() -> JsSerdeError {
+  ser::Error::custom("object keys must be strings")
+}
+
+impl ser::Serializer for KeySerializer {
+  type Ok = String;
+  type Error = JsSerdeError;
+
+  type SerializeSeq = ser::Impossible<String, JsSerdeError>;
+  type SerializeTuple = ser::Impossible<String, JsSerdeError>;
+  type SerializeTupleStruct = ser::Impossible<String, JsSerdeError>;
+  type SerializeTupleVariant = ser::Impossible<String, JsSerdeError>;
+  type SerializeMap = ser::Impossible<String, JsSerdeError>;
+  type SerializeStruct = ser::Impossible<String, JsSerdeError>;
+  type SerializeStructVariant = ser::Impossible<String, JsSerdeError>;
+
+  fn serialize_str(
+    self,
+    v: &str,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_char(
+    self,
+    v: char,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_bool(
+    self,
+    v: bool,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_i8(
+    self,
+    v: i8,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_i16(
+    self,
+    v: i16,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_i32(
+    self,
+    v: i32,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_i64(
+    self,
+    v: i64,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_u8(
+    self,
+    v: u8,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_u16(
+    self,
+    v: u16,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_u32(
+    self,
+    v: u32,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_u64(
+    self,
+    v: u64,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_f32(
+    self,
+    v: f32,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_f64(
+    self,
+    v: f64,
+  ) -> Result<String, JsSerdeError> {
+    Ok(v.to_string())
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+  ) -> Result<String, JsSerdeError> {
+    Ok(variant.to_string())
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    value: &T,
+  ) -> Result<String, JsSerdeError> {
+    value.serialize(self)
+  }
+
+  fn serialize_bytes(
+    self,
+    _v: &[u8],
+  ) -> Result<String, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_none(self) -> Result<String, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(
+    self,
+    _value: &T,
+  ) -> Result<String, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_unit(self) -> Result<String, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_unit_struct(
+    self,
+    _name: &'static str,
+  ) -> Result<String, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _value: &T,
+  ) -> Result<String, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_seq(
+    self,
+    _len: Option<usize>,
+  ) -> Result<Self::SerializeSeq, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_tuple(
+    self,
+    _len: usize,
+  ) -> Result<Self::SerializeTuple, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleStruct, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeTupleVariant, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_map(
+    self,
+    _len: Option<usize>,
+  ) -> Result<Self::SerializeMap, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_struct(
+    self,
+    _name: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStruct, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    _variant: &'static str,
+    _len: usize,
+  ) -> Result<Self::SerializeStructVariant, JsSerdeError> {
+    Err(key_must_be_a_string())
+  }
+}
@@ -1,11 +1,22 @@
+mod config;
+mod graph;
+mod js_serde;
+mod pipeline;
 mod plugins;
 mod register_worker;
-mod register_main;
+mod resolver_host;
 mod public;
+mod spawn_workers;
 mod worker_farm;
 
-use register_main::register_main;
 use register_worker::register_worker;
+use resolver_host::host_build_graph;
+use resolver_host::host_close;
+use resolver_host::host_load;
+use resolver_host::host_new;
+use resolver_host::host_resolve;
+use resolver_host::host_resolve_many;
+use resolver_host::host_transform;
 
 use neon::prelude::*;
 
@@ -14,7 +25,13 @@ use neon::prelude::*;
 fn main(mut cx: ModuleContext) -> NeonResult<()> {
   cx.export_function("register_worker", register_worker)?;
 
-  // This is the true "main()" function
-  cx.export_function("register_main", register_main)?;
+  // The Rust resolver pipeline, wrapped by lib/resolver_host.js
+  cx.export_function("host_new", host_new)?;
+  cx.export_function("host_resolve", host_resolve)?;
+  cx.export_function("host_resolve_many", host_resolve_many)?;
+  cx.export_function("host_transform", host_transform)?;
+  cx.export_function("host_load", host_load)?;
+  cx.export_function("host_build_graph", host_build_graph)?;
+  cx.export_function("host_close", host_close)?;
   Ok(())
 }
@@ -0,0 +1,5 @@
+mod resolver_pipeline;
+mod thread_pool;
+
+pub use crate::pipeline::resolver_pipeline::*;
+pub use crate::pipeline::thread_pool::*;
@@ -0,0 +1,332 @@
+/*
+  Runs a set of resolvers in priority order (lowest first, ties keep
+  the order they were added in) and stops at the first definitive
+  answer. A resolver declines a specifier by returning
+  Resolution::Unresolved, which passes it on to the next resolver.
+  Errors are definitive and stop the pipeline.
+
+  In concurrent mode every resolver is queried at once on a thread
+  pool, the answers are still taken in priority order so the result is
+  the same as running them one after another. This trades extra work
+  in lower priority resolvers for latency when higher priority
+  resolvers are slow (e.g. round trips to Node.js)
+*/
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::public::Resolution;
+use crate::public::ResolveError;
+use crate::public::ResolveOptions;
+use crate::public::ResolveResult;
+use crate::public::Resolver;
+
+use super::ThreadPool;
+
+#[derive(Debug)]
+pub struct ResolverPipeline {
+  // Set when the resolvers are run concurrently
+  pool: Option<ThreadPool>,
+  resolvers: Vec<PipelineEntry>,
+}
+
+#[derive(Debug)]
+struct PipelineEntry {
+  name: String,
+  priority: i32,
+  resolver: Arc<dyn Resolver>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PipelineResult {
+  // Name of the resolver that answered, None when no resolver did
+  pub resolver_name: Option<String>,
+  pub result: ResolveResult,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineError {
+  pub resolver_name: String,
+  pub error: ResolveError,
+}
+
+impl ResolverPipeline {
+  pub fn new(concurrent: bool) -> Self {
+    let pool_size = thread::available_parallelism().map_or(4, |count| count.get());
+    Self {
+      pool: concurrent.then(|| ThreadPool::new(pool_size)),
+      resolvers: vec![],
+    }
+  }
+
+  // The name must be unique, answers refer to the resolver by it
+  pub fn add<R: Resolver + 'static>(
+    &mut self,
+    name: &str,
+    priority: i32,
+    resolver: R,
+  ) {
+    let index = self
+      .resolvers
+      .partition_point(|entry| entry.priority <= priority);
+
+    self.resolvers.insert(
+      index,
+      PipelineEntry {
+        name: name.to_string(),
+        priority,
+        resolver: Arc::new(resolver),
+      },
+    );
+  }
+
+  pub fn resolve(
+    &self,
+    from_path: &Path,
+    specifier: &str,
+    options: &ResolveOptions,
+  ) -> Result<PipelineResult, PipelineError> {
+    if let Some(pool) = &self.pool {
+      return self.resolve_concurrent(pool, from_path, specifier, options);
+    }
+
+    for entry in &self.resolvers {
+      let result = entry.resolver.resolve(from_path, specifier, options);
+      if let Some(answer) = entry.to_answer(result) {
+        return answer;
+      }
+    }
+
+    return Ok(PipelineResult::unresolved());
+  }
+
+  fn resolve_concurrent(
+    &self,
+    pool: &ThreadPool,
+    from_path: &Path,
+    specifier: &str,
+    options: &ResolveOptions,
+  ) -> Result<PipelineResult, PipelineError> {
+    let mut on_results = vec![];
+
+    for entry in &self.resolvers {
+      let (tx, rx) = channel::<Result<ResolveResult, ResolveError>>();
+      let resolver = entry.resolver.clone();
+      let from_path = from_path.to_path_buf();
+      let specifier = specifier.to_string();
+      let options = options.clone();
+
+      pool.spawn(move || {
+        tx.send(resolver.resolve(&from_path, &specifier, &options)).ok();
+      });
+
+      on_results.push(rx);
+    }
+
+    // Resolvers further down that are still running are left to
+    // finish in the background once an answer is found
+    for (entry, rx) in self.resolvers.iter().zip(on_results) {
+      let result = rx.recv().unwrap_or_else(|_| {
+        Err(ResolveError::Unreachable(format!(
+          "Resolver \"{}\" panicked",
+          entry.name
+        )))
+      });
+      if let Some(answer) = entry.to_answer(result) {
+        return answer;
+      }
+    }
+
+    return Ok(PipelineResult::unresolved());
+  }
+}
+
+impl PipelineEntry {
+  // None when the resolver declined and the next one should be tried
+  fn to_answer(
+    &self,
+    result: Result<ResolveResult, ResolveError>,
+  ) -> Option<Result<PipelineResult, PipelineError>> {
+    match result {
+      Ok(result) if result.resolution == Resolution::Unresolved => None,
+      Ok(result) => Some(Ok(PipelineResult {
+        resolver_name: Some(self.name.clone()),
+        result,
+      })),
+      Err(error) => Some(Err(PipelineError {
+        resolver_name: self.name.clone(),
+        error,
+      })),
+    }
+  }
+}
+
+impl PipelineResult {
+  fn unresolved() -> Self {
+    Self {
+      resolver_name: None,
+      result: ResolveResult::default(),
+    }
+  }
+}
+
+impl std::fmt::Display for PipelineError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    write!(f, "{} (resolver \"{}\")", self.error, self.resolver_name)
+  }
+}
+
+impl std::error::Error for PipelineError {}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+  use std::thread;
+  use std::time::Duration;
+
+  use super::*;
+
+  #[derive(Debug)]
+  enum Answer {
+    Decline,
+    Path(&'static str),
+    Fail(&'static str),
+  }
+
+  #[derive(Debug)]
+  struct TestResolver {
+    answer: Answer,
+    delay_ms: u64,
+  }
+
+  impl Resolver for TestResolver {
+    fn resolve(
+      &self,
+      _from_path: &Path,
+      _specifier: &str,
+      _options: &ResolveOptions,
+    ) -> Result<ResolveResult, ResolveError> {
+      thread::sleep(Duration::from_millis(self.delay_ms));
+      return match self.answer {
+        Answer::Decline => Ok(ResolveResult::default()),
+        Answer::Path(file_path) => Ok(ResolveResult::path(PathBuf::from(file_path))),
+        Answer::Fail(message) => Err(ResolveError::Failed(message.to_string())),
+      };
+    }
+  }
+
+  fn pipeline(
+    concurrent: bool,
+    resolvers: Vec<(&str, i32, Answer, u64)>,
+  ) -> ResolverPipeline {
+    let mut pipeline = ResolverPipeline::new(concurrent);
+    for (name, priority, answer, delay_ms) in resolvers {
+      pipeline.add(name, priority, TestResolver { answer, delay_ms });
+    }
+    return pipeline;
+  }
+
+  fn resolve(pipeline: &ResolverPipeline) -> Result<PipelineResult, PipelineError> {
+    return pipeline.resolve(Path::new("/"), "./a.js", &ResolveOptions::default());
+  }
+
+  #[test]
+  fn lowest_priority_answers_first() {
+    let pipeline = pipeline(
+      false,
+      vec![
+        ("late", 2, Answer::Path("/late.js"), 0),
+        ("early", 1, Answer::Path("/early.js"), 0),
+      ],
+    );
+    let result = resolve(&pipeline).unwrap();
+    assert_eq!(result.resolver_name.as_deref(), Some("early"));
+    assert_eq!(
+      result.result.resolution,
+      Resolution::Path(PathBuf::from("/early.js"))
+    );
+  }
+
+  #[test]
+  fn ties_keep_the_order_they_were_added_in() {
+    let pipeline = pipeline(
+      false,
+      vec![
+        ("first", 0, Answer::Path("/first.js"), 0),
+        ("second", 0, Answer::Path("/second.js"), 0),
+      ],
+    );
+    assert_eq!(
+      resolve(&pipeline).unwrap().resolver_name.as_deref(),
+      Some("first")
+    );
+  }
+
+  #[test]
+  fn declining_passes_on_to_the_next_resolver() {
+    let pipeline = pipeline(
+      false,
+      vec![
+        ("declines", 0, Answer::Decline, 0),
+        ("answers", 1, Answer::Path("/a.js"), 0),
+      ],
+    );
+    let result = resolve(&pipeline).unwrap();
+    assert_eq!(result.resolver_name.as_deref(), Some("answers"));
+  }
+
+  #[test]
+  fn unresolved_when_every_resolver_declines() {
+    let pipeline = pipeline(false, vec![("declines", 0, Answer::Decline, 0)]);
+    let result = resolve(&pipeline).unwrap();
+    assert_eq!(result.resolver_name, None);
+    assert_eq!(result.result.resolution, Resolution::Unresolved);
+  }
+
+  #[test]
+  fn errors_stop_the_pipeline() {
+    let pipeline = pipeline(
+      false,
+      vec![
+        ("fails", 0, Answer::Fail("nope"), 0),
+        ("answers", 1, Answer::Path("/a.js"), 0),
+      ],
+    );
+    let error = resolve(&pipeline).unwrap_err();
+    assert_eq!(error.resolver_name, "fails");
+    assert_eq!(error.error, ResolveError::Failed("nope".to_string()));
+  }
+
+  #[test]
+  fn concurrent_answers_in_priority_order() {
+    // The slow resolver comes first so the fast one finishes before it
+    let answers = pipeline(
+      true,
+      vec![
+        ("slow", 0, Answer::Path("/slow.js"), 50),
+        ("fast", 1, Answer::Path("/fast.js"), 0),
+      ],
+    );
+    assert_eq!(
+      resolve(&answers).unwrap().resolver_name.as_deref(),
+      Some("slow")
+    );
+
+    let declines = pipeline(
+      true,
+      vec![
+        ("slow", 0, Answer::Decline, 50),
+        ("fast", 1, Answer::Path("/fast.js"), 0),
+      ],
+    );
+    let result = resolve(&declines).unwrap();
+    assert_eq!(result.resolver_name.as_deref(), Some("fast"));
+  }
+}
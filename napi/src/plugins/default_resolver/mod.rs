@@ -7,8 +7,13 @@
   trait abstraction
 */
 use std::path::Path;
-use std::path::PathBuf;
 
+use crate::public::Environment;
+use crate::public::ImportKind;
+use crate::public::Resolution;
+use crate::public::ResolveError;
+use crate::public::ResolveOptions;
+use crate::public::ResolveResult;
 use crate::public::Resolver;
 
 #[derive(Debug)]
@@ -24,8 +29,31 @@ impl Resolver for DefaultResolver {
   fn resolve(
     &self,
     _from_path: &Path,
-    _specifier: &str,
-  ) -> Option<PathBuf> {
-    return None;
+    specifier: &str,
+    options: &ResolveOptions,
+  ) -> Result<ResolveResult, ResolveError> {
+    // Node.js builtins only exist when targeting Node.js
+    if options.environment == Environment::Node {
+      if let Some(name) = specifier.strip_prefix("node:") {
+        return Ok(ResolveResult {
+          resolution: Resolution::Builtin(name.to_string()),
+          ..Default::default()
+        });
+      }
+    }
+
+    // Remote and inline assets referenced by url() are left untouched
+    if options.kind == ImportKind::Url
+      && ["http://", "https://", "data:"]
+        .iter()
+        .any(|prefix| specifier.starts_with(prefix))
+    {
+      return Ok(ResolveResult {
+        resolution: Resolution::External,
+        ..Default::default()
+      });
+    }
+
+    return Ok(ResolveResult::default());
   }
 }
@@ -0,0 +1,155 @@
+/*
+  This is a "Plugin Proxy" that runs functions in the
+  Node workers remotely via the NodeWorkerFarm, translating
+  the requests/responses to match the interface of the
+  internal "Loader" trait
+*/
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::public::LoadError;
+use crate::public::LoadOutput;
+use crate::public::Loader;
+use crate::public::ResolvedId;
+
+use crate::worker_farm::LoadLoaderRequest;
+use crate::worker_farm::NodeWorkerFarm;
+use crate::worker_farm::PluginHandle;
+use crate::worker_farm::PluginRequest;
+use crate::worker_farm::PluginResponse;
+use crate::worker_farm::RunLoaderRequest;
+use crate::worker_farm::RunLoaderResponse;
+
+use super::PluginLoadError;
+
+#[derive(Debug)]
+pub struct LoaderNodeProxy {
+  specifier: String,
+  handle: PluginHandle,
+  worker_farm: Arc<NodeWorkerFarm>,
+}
+
+impl LoaderNodeProxy {
+  pub fn new(
+    worker_farm: Arc<NodeWorkerFarm>,
+    specifier: &str,
+    config_path: &Path,
+  ) -> Result<Self, PluginLoadError> {
+    let handle = worker_farm.new_plugin_handle();
+    let Ok(responses) = worker_farm.load_all_blocking(PluginRequest::LoadLoader(
+      handle,
+      LoadLoaderRequest {
+        specifier: specifier.to_string(),
+        config_path: config_path.to_path_buf(),
+      },
+    )) else {
+      return Err(PluginLoadError::Unreachable {
+        specifier: specifier.to_string(),
+      });
+    };
+
+    for response in responses {
+      match response {
+        PluginResponse::LoadLoader => {}
+        PluginResponse::Error(error) => {
+          return Err(PluginLoadError::Failed {
+            specifier: specifier.to_string(),
+            message: error.message,
+          })
+        }
+        response => {
+          return Err(PluginLoadError::Failed {
+            specifier: specifier.to_string(),
+            message: format!("Unexpected response {:?}", response),
+          })
+        }
+      }
+    }
+
+    return Ok(Self {
+      worker_farm,
+      specifier: specifier.to_string(),
+      handle,
+    });
+  }
+}
+
+impl Loader for LoaderNodeProxy {
+  fn load(
+    &self,
+    resolved_id: &ResolvedId,
+  ) -> Result<Option<LoadOutput>, LoadError> {
+    let (file_path, virtual_id) = match resolved_id {
+      ResolvedId::Path(file_path) => (Some(file_path.clone()), None),
+      ResolvedId::Virtual(virtual_id) => (None, Some(virtual_id.clone())),
+    };
+
+    let Ok(response) = self
+      .worker_farm
+      .send_blocking(PluginRequest::RunLoader(
+        self.handle,
+        RunLoaderRequest {
+          file_path,
+          virtual_id,
+        },
+      ))
+    else {
+      return Err(LoadError::Unreachable(format!(
+        "No response from loader \"{}\"",
+        self.specifier
+      )));
+    };
+
+    let response = match response {
+      PluginResponse::RunLoader(response) => response,
+      PluginResponse::Error(error) => return Err(LoadError::Failed(error.to_string())),
+      response => {
+        return Err(LoadError::Failed(format!(
+          "Unexpected response from loader \"{}\": {:?}",
+          self.specifier, response
+        )))
+      }
+    };
+
+    // JS loaders return null to pass on to the next loader
+    let Some(response) = response else {
+      return Ok(None);
+    };
+
+    return into_output(response, resolved_id);
+  }
+}
+
+/*
+  The object a JS loader returns (or null), every field is optional,
+  without code the module is passed on to the next loader:
+  {
+    code?: string | Buffer,
+    source_map?: string,
+    error?: string,
+  }
+*/
+fn into_output(
+  response: RunLoaderResponse,
+  resolved_id: &ResolvedId,
+) -> Result<Option<LoadOutput>, LoadError> {
+  if let Some(error) = response.error {
+    return Err(LoadError::Failed(error));
+  }
+
+  let Some(code) = response.code else {
+    return Ok(None);
+  };
+
+  let Ok(code) = String::from_utf8(code.into_vec()) else {
+    return Err(LoadError::Failed(format!(
+      "The code returned for \"{}\" is not UTF-8",
+      resolved_id
+    )));
+  };
+
+  return Ok(Some(LoadOutput {
+    code,
+    source_map: response.source_map,
+  }));
+}
@@ -1,3 +1,11 @@
+mod loader;
+mod plugin_exports;
+mod reporter;
 mod resolver;
+mod transformer;
 
+pub use crate::plugins::node_proxy::loader::*;
+pub use crate::plugins::node_proxy::plugin_exports::*;
+pub use crate::plugins::node_proxy::reporter::*;
 pub use crate::plugins::node_proxy::resolver::*;
+pub use crate::plugins::node_proxy::transformer::*;
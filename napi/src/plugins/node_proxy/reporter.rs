@@ -0,0 +1,89 @@
+/*
+  This is a "Plugin Proxy" that runs functions in the
+  Node workers remotely via the NodeWorkerFarm, translating
+  the requests/responses to match the interface of the
+  internal "Reporter" trait
+
+  Events are sent as notifications to a single worker so they
+  arrive in order and nothing waits on them
+*/
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::public::Reporter;
+use crate::public::ReporterEvent;
+
+use crate::worker_farm::LoadReporterRequest;
+use crate::worker_farm::NodeWorkerFarm;
+use crate::worker_farm::PluginHandle;
+use crate::worker_farm::PluginRequest;
+use crate::worker_farm::PluginResponse;
+
+use super::PluginLoadError;
+
+// Every worker loads the reporter but only this one is sent events
+const REPORTER_WORKER: usize = 0;
+
+#[derive(Debug)]
+pub struct ReporterNodeProxy {
+  handle: PluginHandle,
+  worker_farm: Arc<NodeWorkerFarm>,
+}
+
+impl ReporterNodeProxy {
+  pub fn new(
+    worker_farm: Arc<NodeWorkerFarm>,
+    specifier: &str,
+    config_path: &Path,
+  ) -> Result<Self, PluginLoadError> {
+    let handle = worker_farm.new_plugin_handle();
+    let Ok(responses) = worker_farm.load_all_blocking(PluginRequest::LoadReporter(
+      handle,
+      LoadReporterRequest {
+        specifier: specifier.to_string(),
+        config_path: config_path.to_path_buf(),
+      },
+    )) else {
+      return Err(PluginLoadError::Unreachable {
+        specifier: specifier.to_string(),
+      });
+    };
+
+    for response in responses {
+      match response {
+        PluginResponse::LoadReporter => {}
+        PluginResponse::Error(error) => {
+          return Err(PluginLoadError::Failed {
+            specifier: specifier.to_string(),
+            message: error.message,
+          })
+        }
+        response => {
+          return Err(PluginLoadError::Failed {
+            specifier: specifier.to_string(),
+            message: format!("Unexpected response {:?}", response),
+          })
+        }
+      }
+    }
+
+    return Ok(Self {
+      worker_farm,
+      handle,
+    });
+  }
+}
+
+impl Reporter for ReporterNodeProxy {
+  fn report(
+    &self,
+    event: &ReporterEvent,
+  ) {
+    // Events sent while the worker is restarting, or after the farm is
+    // disposed, have no reporter to go to
+    self
+      .worker_farm
+      .notify(REPORTER_WORKER, PluginRequest::RunReporter(self.handle, event.clone()))
+      .ok();
+  }
+}
@@ -3,22 +3,33 @@
   Node workers remotely via the NodeInstance, translating
   the requests/responses to match the interface of the 
   internal "Resolver" trait
+
+  Each proxy loads its own instance of the plugin with its own
+  options, the workers know it by its PluginHandle
 */
 use std::path::Path;
-use std::path::PathBuf;
 use std::sync::Arc;
 
+use crate::public::Resolution;
+use crate::public::ResolveError;
+use crate::public::ResolveOptions;
+use crate::public::ResolveResult;
 use crate::public::Resolver;
 
 use crate::worker_farm::LoadResolverRequest;
 use crate::worker_farm::NodeWorkerFarm;
+use crate::worker_farm::PluginHandle;
 use crate::worker_farm::PluginRequest;
 use crate::worker_farm::PluginResponse;
 use crate::worker_farm::RunResolverRequest;
+use crate::worker_farm::RunResolverResponse;
+
+use super::PluginLoadError;
 
 #[derive(Debug)]
 pub struct ResolverNodeProxy {
-  key: String,
+  specifier: String,
+  handle: PluginHandle,
   worker_farm: Arc<NodeWorkerFarm>,
 }
 
@@ -26,17 +37,60 @@ impl ResolverNodeProxy {
   pub fn new(
     worker_farm: Arc<NodeWorkerFarm>,
     specifier: &str,
-  ) -> Self {
-    worker_farm
-      .send_all(PluginRequest::LoadResolver(LoadResolverRequest {
+    config_path: &Path,
+    options: &serde_json::Value,
+  ) -> Result<Self, PluginLoadError> {
+    let handle = worker_farm.new_plugin_handle();
+    let Ok(responses) = worker_farm.load_all_blocking(PluginRequest::LoadResolver(
+      handle,
+      LoadResolverRequest {
+        specifier: specifier.to_string(),
+        config_path: config_path.to_path_buf(),
+        options: options.clone(),
+      },
+    )) else {
+      return Err(PluginLoadError::Unreachable {
         specifier: specifier.to_string(),
-      }))
-      .unwrap();
+      });
+    };
 
-    Self {
-      worker_farm,
-      key: specifier.to_string(),
+    // Every worker loads the same module, any of them can describe it
+    let mut exports = None;
+    for response in responses {
+      match response {
+        PluginResponse::LoadResolver(response) => exports = Some(response),
+        PluginResponse::Error(error) => {
+          return Err(PluginLoadError::Failed {
+            specifier: specifier.to_string(),
+            message: error.message,
+          })
+        }
+        response => {
+          return Err(PluginLoadError::Failed {
+            specifier: specifier.to_string(),
+            message: format!("Unexpected response {:?}", response),
+          })
+        }
+      }
+    }
+    let Some(exports) = exports else {
+      return Err(PluginLoadError::Unreachable {
+        specifier: specifier.to_string(),
+      });
+    };
+
+    if let Err(problem) = exports.check("resolve") {
+      return Err(PluginLoadError::InvalidExports {
+        specifier: specifier.to_string(),
+        problem,
+      });
     }
+
+    return Ok(Self {
+      worker_farm,
+      specifier: specifier.to_string(),
+      handle,
+    });
   }
 }
 
@@ -45,22 +99,83 @@ impl Resolver for ResolverNodeProxy {
     &self,
     from_path: &Path,
     specifier: &str,
-  ) -> Option<PathBuf> {
-    let response = self
+    options: &ResolveOptions,
+  ) -> Result<ResolveResult, ResolveError> {
+    let Ok(response) = self
       .worker_farm
       .send_blocking(PluginRequest::RunResolver(
-        self.key.clone(),
+        self.handle,
         RunResolverRequest {
           from_path: from_path.to_path_buf(),
           specifier: specifier.to_string(),
+          options: options.clone(),
         },
       ))
-      .unwrap();
+    else {
+      return Err(ResolveError::Unreachable(format!(
+        "No response from resolver \"{}\"",
+        self.specifier
+      )));
+    };
+
+    let response = match response {
+      PluginResponse::RunResolver(response) => response,
+      PluginResponse::Error(error) => return Err(ResolveError::Failed(error.to_string())),
+      response => {
+        return Err(ResolveError::Failed(format!(
+          "Unexpected response from resolver \"{}\": {:?}",
+          self.specifier, response
+        )))
+      }
+    };
 
-    let PluginResponse::RunResolver(response) = response else {
-      panic!("should not");
+    // JS resolvers return null to pass on to the next resolver
+    let Some(response) = response else {
+      return Ok(ResolveResult::default());
     };
 
-    return Some(response.file_path);
+    return into_result(response);
   }
 }
+
+/*
+  The object a JS resolver returns (or null), every field is optional:
+  {
+    file_path?: string,
+    virtual_id?: { namespace: string, id: string },
+    external?: boolean,
+    builtin?: string,
+    side_effects?: boolean,
+    module_type?: "esm" | "cjs",
+    query?: string,
+    invalidate_on_file_change?: string[],
+    invalidate_on_file_create?: string[],
+    error?: string,
+  }
+*/
+fn into_result(response: RunResolverResponse) -> Result<ResolveResult, ResolveError> {
+  if let Some(error) = response.error {
+    return Err(ResolveError::Failed(error));
+  }
+
+  let resolution = if let Some(builtin) = response.builtin {
+    Resolution::Builtin(builtin)
+  } else if response.external {
+    Resolution::External
+  } else if let Some(virtual_id) = response.virtual_id {
+    Resolution::Virtual(virtual_id)
+  } else if let Some(file_path) = response.file_path {
+    Resolution::Path(file_path)
+  } else {
+    Resolution::Unresolved
+  };
+
+  return Ok(ResolveResult {
+    resolution,
+    side_effects: response.side_effects.unwrap_or(true),
+    module_type: response.module_type,
+    query: response.query,
+    invalidate_on_file_change: response.invalidate_on_file_change,
+    invalidate_on_file_create: response.invalidate_on_file_create,
+  });
+}
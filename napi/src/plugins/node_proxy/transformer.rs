@@ -0,0 +1,159 @@
+/*
+  This is a "Plugin Proxy" that runs functions in the
+  Node workers remotely via the NodeWorkerFarm, translating
+  the requests/responses to match the interface of the
+  internal "Transformer" trait
+
+  The code goes to JS as a Buffer without being copied
+*/
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::js_serde::Bytes;
+use crate::public::TransformError;
+use crate::public::TransformInput;
+use crate::public::TransformOutput;
+use crate::public::Transformer;
+
+use crate::worker_farm::LoadTransformerRequest;
+use crate::worker_farm::NodeWorkerFarm;
+use crate::worker_farm::PluginHandle;
+use crate::worker_farm::PluginRequest;
+use crate::worker_farm::PluginResponse;
+use crate::worker_farm::RunTransformerRequest;
+use crate::worker_farm::RunTransformerResponse;
+
+use super::PluginLoadError;
+
+#[derive(Debug)]
+pub struct TransformerNodeProxy {
+  specifier: String,
+  handle: PluginHandle,
+  worker_farm: Arc<NodeWorkerFarm>,
+}
+
+impl TransformerNodeProxy {
+  pub fn new(
+    worker_farm: Arc<NodeWorkerFarm>,
+    specifier: &str,
+    config_path: &Path,
+  ) -> Result<Self, PluginLoadError> {
+    let handle = worker_farm.new_plugin_handle();
+    let Ok(responses) = worker_farm.load_all_blocking(PluginRequest::LoadTransformer(
+      handle,
+      LoadTransformerRequest {
+        specifier: specifier.to_string(),
+        config_path: config_path.to_path_buf(),
+      },
+    )) else {
+      return Err(PluginLoadError::Unreachable {
+        specifier: specifier.to_string(),
+      });
+    };
+
+    for response in responses {
+      match response {
+        PluginResponse::LoadTransformer => {}
+        PluginResponse::Error(error) => {
+          return Err(PluginLoadError::Failed {
+            specifier: specifier.to_string(),
+            message: error.message,
+          })
+        }
+        response => {
+          return Err(PluginLoadError::Failed {
+            specifier: specifier.to_string(),
+            message: format!("Unexpected response {:?}", response),
+          })
+        }
+      }
+    }
+
+    return Ok(Self {
+      worker_farm,
+      specifier: specifier.to_string(),
+      handle,
+    });
+  }
+}
+
+impl Transformer for TransformerNodeProxy {
+  fn transform(
+    &self,
+    input: &TransformInput,
+  ) -> Result<TransformOutput, TransformError> {
+    let Ok(response) = self
+      .worker_farm
+      .send_blocking(PluginRequest::RunTransformer(
+        self.handle,
+        RunTransformerRequest {
+          file_path: input.file_path.clone(),
+          code: Bytes::from(input.code.clone()),
+          source_map: input.source_map.clone(),
+          options: input.options.clone(),
+        },
+      ))
+    else {
+      return Err(TransformError::Unreachable(format!(
+        "No response from transformer \"{}\"",
+        self.specifier
+      )));
+    };
+
+    let response = match response {
+      PluginResponse::RunTransformer(response) => response,
+      PluginResponse::Error(error) => return Err(TransformError::Failed(error.to_string())),
+      response => {
+        return Err(TransformError::Failed(format!(
+          "Unexpected response from transformer \"{}\": {:?}",
+          self.specifier, response
+        )))
+      }
+    };
+
+    // JS transformers return null to leave the code as it is
+    let Some(response) = response else {
+      return Ok(TransformOutput::unchanged(input));
+    };
+
+    return into_output(response, input);
+  }
+}
+
+/*
+  The object a JS transformer returns (or null), every field is optional:
+  {
+    code?: string | Buffer,
+    source_map?: string,
+    dependencies?: { specifier: string, kind?: "import" | "require" | ... }[],
+    error?: string,
+  }
+*/
+fn into_output(
+  response: RunTransformerResponse,
+  input: &TransformInput,
+) -> Result<TransformOutput, TransformError> {
+  if let Some(error) = response.error {
+    return Err(TransformError::Failed(error));
+  }
+
+  // The input's map only carries over with its code
+  let (code, source_map) = match response.code {
+    Some(code) => match String::from_utf8(code.into_vec()) {
+      Ok(code) => (code, response.source_map),
+      Err(_) => {
+        return Err(TransformError::Failed(format!(
+          "The code returned for \"{}\" is not UTF-8",
+          input.file_path.display()
+        )))
+      }
+    },
+    None => (input.code.clone(), input.source_map.clone()),
+  };
+
+  return Ok(TransformOutput {
+    code,
+    source_map,
+    dependencies: response.dependencies,
+  });
+}
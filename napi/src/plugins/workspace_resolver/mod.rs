@@ -0,0 +1,374 @@
+/*
+  Resolves bare specifiers that name a package in the surrounding
+  npm/yarn/pnpm workspace directly to that package's directory, skipping
+  node_modules symlinks and built output.
+
+  Targets may leave out the extension ("pkg/utils/foo" for
+  "utils/foo.ts") or name a directory with an index file, see
+  resolve_file.
+
+  When "prefer_source" is set, a package's "source" field is used in
+  place of its "main" field so imports point at the original sources
+  rather than the build artifacts.
+
+  The workspace root of each directory and the index of each workspace
+  are looked up once and kept for the lifetime of the resolver host,
+  a new host picks up changes to the workspace.
+*/
+mod workspace_index;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use crate::public::ResolveError;
+use crate::public::ResolveOptions;
+use crate::public::ResolveResult;
+use crate::public::Resolver;
+
+use self::workspace_index::WorkspaceIndex;
+
+#[derive(Debug)]
+pub struct WorkspaceResolver {
+  prefer_source: bool,
+  // The workspace root each directory belongs to, None outside of a workspace
+  roots: RwLock<HashMap<PathBuf, Option<PathBuf>>>,
+  indexes: RwLock<HashMap<PathBuf, Arc<WorkspaceIndex>>>,
+}
+
+impl WorkspaceResolver {
+  pub fn new(prefer_source: bool) -> Self {
+    Self {
+      prefer_source,
+      roots: RwLock::new(HashMap::new()),
+      indexes: RwLock::new(HashMap::new()),
+    }
+  }
+
+  // The filesystem is only touched on a miss and never while a lock
+  // is held. Two threads missing at once both build the index and
+  // the first one to finish is kept
+  fn get_index(
+    &self,
+    from_path: &Path,
+  ) -> Option<Arc<WorkspaceIndex>> {
+    let root = self.get_root(from_path)?;
+
+    if let Some(index) = self.indexes.read().unwrap().get(&root) {
+      return Some(index.clone());
+    }
+
+    let index = Arc::new(WorkspaceIndex::build(&root));
+    let mut indexes = self.indexes.write().unwrap();
+    return Some(indexes.entry(root).or_insert(index).clone());
+  }
+
+  fn get_root(
+    &self,
+    from_path: &Path,
+  ) -> Option<PathBuf> {
+    if let Some(root) = self.roots.read().unwrap().get(from_path) {
+      return root.clone();
+    }
+
+    let root = WorkspaceIndex::find_root(from_path);
+    let mut roots = self.roots.write().unwrap();
+    return roots.entry(from_path.to_path_buf()).or_insert(root).clone();
+  }
+}
+
+impl Resolver for WorkspaceResolver {
+  fn resolve(
+    &self,
+    from_path: &Path,
+    specifier: &str,
+    _options: &ResolveOptions,
+  ) -> Result<ResolveResult, ResolveError> {
+    let Some((package_name, subpath)) = parse_bare_specifier(specifier) else {
+      return Ok(ResolveResult::default());
+    };
+    let Some(index) = self.get_index(from_path) else {
+      return Ok(ResolveResult::default());
+    };
+    let Some(package) = index.packages.get(package_name) else {
+      return Ok(ResolveResult::default());
+    };
+
+    let (file_path, missing) = resolve_file(&package.entry(subpath, self.prefer_source));
+
+    return Ok(ResolveResult {
+      side_effects: package.side_effects,
+      module_type: package.module_type,
+      invalidate_on_file_change: vec![package.dir.join("package.json")],
+      invalidate_on_file_create: missing,
+      ..ResolveResult::path(file_path)
+    });
+  }
+}
+
+// Splits "@scope/pkg/sub/path" into ("@scope/pkg", "sub/path"),
+// returns None for relative, absolute and protocol specifiers
+fn parse_bare_specifier(specifier: &str) -> Option<(&str, &str)> {
+  if specifier.is_empty() || specifier.starts_with(['.', '/']) || specifier.contains(':') {
+    return None;
+  }
+
+  let name_segments = if specifier.starts_with('@') { 2 } else { 1 };
+  let mut end = 0;
+
+  for (i, segment) in specifier.split('/').enumerate() {
+    if i == name_segments {
+      break;
+    }
+    end += segment.len() + if i == 0 { 0 } else { 1 };
+  }
+
+  let package_name = &specifier[..end];
+  let subpath = specifier[end..].trim_start_matches('/');
+  return Some((package_name, subpath));
+}
+
+// Tried in order when a target leaves out the extension
+const EXTENSIONS: [&str; 7] = [".tsx", ".ts", ".jsx", ".js", ".mjs", ".cjs", ".json"];
+
+// The file a package target points to: the target itself, then with
+// each extension added, then the index file of the directory. Returns
+// it with the candidates tried before it, creating one of those would
+// change the answer. Falls back to the target when none exist
+fn resolve_file(target: &Path) -> (PathBuf, Vec<PathBuf>) {
+  let index = target.join("index");
+  let candidates = std::iter::once(target.to_path_buf())
+    .chain(EXTENSIONS.iter().map(|extension| with_suffix(target, extension)))
+    .chain(EXTENSIONS.iter().map(|extension| with_suffix(&index, extension)));
+
+  let mut missing = Vec::<PathBuf>::new();
+  for candidate in candidates {
+    if candidate.is_file() {
+      return (candidate, missing);
+    }
+    missing.push(candidate);
+  }
+  return (target.to_path_buf(), missing);
+}
+
+// "utils/foo" + ".ts" -> "utils/foo.ts", keeping any dots in the name
+fn with_suffix(
+  path: &Path,
+  suffix: &str,
+) -> PathBuf {
+  let mut path = path.as_os_str().to_os_string();
+  path.push(suffix);
+  return PathBuf::from(path);
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env;
+  use std::fs;
+  use std::process;
+
+  use crate::public::ModuleType;
+  use crate::public::Resolution;
+
+  use super::*;
+
+  // Writes the files of a workspace into a fresh directory
+  fn fixture(
+    name: &str,
+    files: &[(&str, &str)],
+  ) -> PathBuf {
+    let dir = env::temp_dir().join(format!("mach-workspace-{}-{}", process::id(), name));
+    fs::remove_dir_all(&dir).ok();
+    for (file_path, contents) in files {
+      let file_path = dir.join(file_path);
+      fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+      fs::write(file_path, contents).unwrap();
+    }
+    return dir;
+  }
+
+  fn resolve(
+    resolver: &WorkspaceResolver,
+    from_path: &Path,
+    specifier: &str,
+  ) -> ResolveResult {
+    return resolver
+      .resolve(from_path, specifier, &ResolveOptions::default())
+      .unwrap();
+  }
+
+  fn resolution(
+    resolver: &WorkspaceResolver,
+    from_path: &Path,
+    specifier: &str,
+  ) -> Resolution {
+    return resolve(resolver, from_path, specifier).resolution;
+  }
+
+  #[test]
+  fn npm_workspaces_resolve_to_the_package() {
+    let dir = fixture(
+      "npm",
+      &[
+        ("package.json", r#"{ "workspaces": ["packages/*"] }"#),
+        (
+          "packages/a/package.json",
+          r#"{ "name": "a", "main": "dist/index.js", "source": "src/index.ts", "type": "module", "sideEffects": false }"#,
+        ),
+        ("packages/a/dist/index.js", ""),
+        ("packages/a/src/index.ts", ""),
+        ("packages/b/src/b.js", ""),
+      ],
+    );
+    let from_path = dir.join("packages/b/src");
+
+    let result = resolve(&WorkspaceResolver::new(false), &from_path, "a");
+    assert_eq!(
+      result.resolution,
+      Resolution::Path(dir.join("packages/a/dist/index.js"))
+    );
+    assert_eq!(result.module_type, Some(ModuleType::Esm));
+    assert!(!result.side_effects);
+    assert_eq!(
+      result.invalidate_on_file_change,
+      vec![dir.join("packages/a/package.json")]
+    );
+
+    assert_eq!(
+      resolution(&WorkspaceResolver::new(true), &from_path, "a"),
+      Resolution::Path(dir.join("packages/a/src/index.ts"))
+    );
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn yarn_workspaces_accept_a_packages_list() {
+    let dir = fixture(
+      "yarn",
+      &[
+        (
+          "package.json",
+          r#"{ "workspaces": { "packages": ["libs/*"] } }"#,
+        ),
+        ("libs/ui/package.json", r#"{ "name": "@scope/ui" }"#),
+        ("libs/ui/index.js", ""),
+      ],
+    );
+    let resolver = WorkspaceResolver::new(false);
+
+    assert_eq!(
+      resolution(&resolver, &dir, "@scope/ui"),
+      Resolution::Path(dir.join("libs/ui/index.js"))
+    );
+    assert_eq!(resolution(&resolver, &dir, "react"), Resolution::Unresolved);
+    assert_eq!(resolution(&resolver, &dir, "./ui"), Resolution::Unresolved);
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn pnpm_workspaces_honour_exclusions() {
+    let dir = fixture(
+      "pnpm",
+      &[
+        (
+          "pnpm-workspace.yaml",
+          "packages:\n  - 'packages/*'\n  - '!packages/private'\n",
+        ),
+        (
+          "packages/a/package.json",
+          r#"{ "name": "a", "main": "main.js" }"#,
+        ),
+        ("packages/a/main.js", ""),
+        ("packages/private/package.json", r#"{ "name": "private" }"#),
+      ],
+    );
+    let resolver = WorkspaceResolver::new(false);
+
+    assert_eq!(
+      resolution(&resolver, &dir, "a"),
+      Resolution::Path(dir.join("packages/a/main.js"))
+    );
+    assert_eq!(
+      resolution(&resolver, &dir, "private"),
+      Resolution::Unresolved
+    );
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn subpaths_are_given_an_extension_or_index_file() {
+    let dir = fixture(
+      "subpaths",
+      &[
+        ("package.json", r#"{ "workspaces": ["packages/*"] }"#),
+        (
+          "packages/a/package.json",
+          r#"{ "name": "a", "main": "lib/main" }"#,
+        ),
+        ("packages/a/lib/main.js", ""),
+        ("packages/a/utils/foo.ts", ""),
+        ("packages/a/utils/index.js", ""),
+        ("packages/a/data.json", ""),
+      ],
+    );
+    let package_dir = dir.join("packages/a");
+    let resolver = WorkspaceResolver::new(false);
+
+    let result = resolve(&resolver, &dir, "a/utils/foo");
+    assert_eq!(
+      result.resolution,
+      Resolution::Path(package_dir.join("utils/foo.ts"))
+    );
+    assert_eq!(
+      result.invalidate_on_file_create,
+      vec![
+        package_dir.join("utils/foo"),
+        package_dir.join("utils/foo.tsx")
+      ]
+    );
+
+    assert_eq!(
+      resolution(&resolver, &dir, "a/utils"),
+      Resolution::Path(package_dir.join("utils/index.js"))
+    );
+    assert_eq!(
+      resolution(&resolver, &dir, "a/data.json"),
+      Resolution::Path(package_dir.join("data.json"))
+    );
+    assert_eq!(
+      resolution(&resolver, &dir, "a"),
+      Resolution::Path(package_dir.join("lib/main.js"))
+    );
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn unreadable_workspaces_are_cached_as_empty() {
+    let dir = fixture(
+      "broken",
+      &[
+        ("pnpm-workspace.yaml", "packages: ["),
+        ("packages/a/package.json", r#"{ "name": "a" }"#),
+        ("packages/a/index.js", ""),
+      ],
+    );
+    let resolver = WorkspaceResolver::new(false);
+    assert_eq!(resolution(&resolver, &dir, "a"), Resolution::Unresolved);
+
+    // Fixing the manifest is only noticed by a new resolver host
+    fs::write(
+      dir.join("pnpm-workspace.yaml"),
+      "packages:\n  - 'packages/*'\n",
+    )
+    .unwrap();
+    assert_eq!(resolution(&resolver, &dir, "a"), Resolution::Unresolved);
+
+    fs::remove_dir_all(&dir).ok();
+  }
+}
@@ -0,0 +1,185 @@
+/*
+  An index of every package in an npm/yarn/pnpm workspace keyed
+  by package name.
+
+  The workspace root is a directory containing either a
+  pnpm-workspace.yaml or a package.json with a "workspaces" field.
+  The glob patterns listed there are expanded to find each package.json
+  in the workspace.
+*/
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::public::ModuleType;
+
+#[derive(Debug)]
+pub struct WorkspaceIndex {
+  pub packages: HashMap<String, WorkspacePackage>,
+}
+
+#[derive(Debug)]
+pub struct WorkspacePackage {
+  pub dir: PathBuf,
+  pub main: Option<String>,
+  pub source: Option<String>,
+  pub module_type: Option<ModuleType>,
+  pub side_effects: bool,
+}
+
+impl WorkspaceIndex {
+  // Walk up from the importing path to find the nearest workspace root
+  pub fn find_root(from_path: &Path) -> Option<PathBuf> {
+    for dir in from_path.ancestors() {
+      if dir.join("pnpm-workspace.yaml").is_file() {
+        return Some(dir.to_path_buf());
+      }
+      let Some(manifest) = read_package_json(&dir.join("package.json")) else {
+        continue;
+      };
+      if manifest.workspaces.is_some() {
+        return Some(dir.to_path_buf());
+      }
+    }
+    return None;
+  }
+
+  // A workspace whose manifest can't be read has no packages, the
+  // index is kept all the same so the manifest isn't read on every lookup
+  pub fn build(root: &Path) -> Self {
+    let pnpm_workspace = root.join("pnpm-workspace.yaml");
+    let root_package_json = root.join("package.json");
+
+    let patterns = if pnpm_workspace.is_file() {
+      read_pnpm_workspace(&pnpm_workspace).map(|config| config.packages)
+    } else {
+      read_package_json(&root_package_json)
+        .and_then(|manifest| manifest.workspaces)
+        .map(Workspaces::into_patterns)
+    };
+
+    let mut packages = HashMap::<String, WorkspacePackage>::new();
+    let (include, exclude): (Vec<String>, Vec<String>) = patterns
+      .unwrap_or_default()
+      .into_iter()
+      .partition(|p| !p.starts_with('!'));
+
+    let exclude = exclude
+      .iter()
+      .filter_map(|p| glob::Pattern::new(&root.join(&p[1..]).to_string_lossy()).ok())
+      .collect::<Vec<glob::Pattern>>();
+
+    for pattern in include {
+      let pattern = root.join(&pattern).join("package.json");
+      let Ok(entries) = glob::glob(&pattern.to_string_lossy()) else {
+        continue;
+      };
+
+      for manifest_path in entries.flatten() {
+        let dir = manifest_path.parent().unwrap().to_path_buf();
+        if dir.components().any(|c| c.as_os_str() == "node_modules") {
+          continue;
+        }
+        if exclude.iter().any(|p| p.matches_path(&dir)) {
+          continue;
+        }
+        let Some(manifest) = read_package_json(&manifest_path) else {
+          continue;
+        };
+        let Some(name) = manifest.name else {
+          continue;
+        };
+        packages.insert(
+          name,
+          WorkspacePackage {
+            dir,
+            main: manifest.main,
+            source: manifest.source,
+            module_type: match manifest.module_type.as_deref() {
+              Some("module") => Some(ModuleType::Esm),
+              Some("commonjs") => Some(ModuleType::Cjs),
+              _ => None,
+            },
+            // "sideEffects" may also be a list of globs, which is
+            // treated as "has side effects"
+            side_effects: manifest.side_effects != Some(serde_json::Value::Bool(false)),
+          },
+        );
+      }
+    }
+
+    return Self { packages };
+  }
+}
+
+impl WorkspacePackage {
+  // The file a specifier naming this package points to. A subpath
+  // ("pkg/utils/foo") is taken relative to the package directory
+  pub fn entry(
+    &self,
+    subpath: &str,
+    prefer_source: bool,
+  ) -> PathBuf {
+    if !subpath.is_empty() {
+      return self.dir.join(subpath);
+    }
+    if prefer_source {
+      if let Some(source) = &self.source {
+        return self.dir.join(source);
+      }
+    }
+    if let Some(main) = &self.main {
+      return self.dir.join(main);
+    }
+    return self.dir.join("index.js");
+  }
+}
+
+#[derive(Deserialize)]
+struct PackageJson {
+  name: Option<String>,
+  main: Option<String>,
+  source: Option<String>,
+  #[serde(rename = "type")]
+  module_type: Option<String>,
+  #[serde(rename = "sideEffects")]
+  side_effects: Option<serde_json::Value>,
+  workspaces: Option<Workspaces>,
+}
+
+// npm and yarn accept either a list of patterns or,
+// for yarn classic, an object with a "packages" list
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Workspaces {
+  Patterns(Vec<String>),
+  Config { packages: Vec<String> },
+}
+
+impl Workspaces {
+  fn into_patterns(self) -> Vec<String> {
+    match self {
+      Workspaces::Patterns(patterns) => patterns,
+      Workspaces::Config { packages } => packages,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct PnpmWorkspace {
+  #[serde(default)]
+  packages: Vec<String>,
+}
+
+fn read_package_json(path: &Path) -> Option<PackageJson> {
+  let contents = fs::read_to_string(path).ok()?;
+  return serde_json::from_str::<PackageJson>(&contents).ok();
+}
+
+fn read_pnpm_workspace(path: &Path) -> Option<PnpmWorkspace> {
+  let contents = fs::read_to_string(path).ok()?;
+  return serde_yaml::from_str::<PnpmWorkspace>(&contents).ok();
+}
@@ -0,0 +1,49 @@
+/*
+  This is the common interface for "Loader" plugins
+
+  Loaders supply the content of a resolved module. Virtual modules
+  have no file so a Loader has to claim them, loaders return None
+  for ids they do not handle
+*/
+use std::fmt::Debug;
+use std::fmt::Display;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::ResolvedId;
+
+pub trait Loader: Sync + Send + Debug {
+  fn load(
+    &self,
+    resolved_id: &ResolvedId,
+  ) -> Result<Option<LoadOutput>, LoadError>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoadOutput {
+  pub code: String,
+  pub source_map: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+  // The loader ran and reported that it failed
+  Failed(String),
+  // The loader could not be reached, e.g. a Node.js worker went away
+  Unreachable(String),
+}
+
+impl Display for LoadError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      LoadError::Failed(message) => write!(f, "Load failed: {}", message),
+      LoadError::Unreachable(message) => write!(f, "Loader unreachable: {}", message),
+    }
+  }
+}
+
+impl std::error::Error for LoadError {}
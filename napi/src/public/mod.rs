@@ -1,3 +1,9 @@
+mod loader;
+mod reporter;
 mod resolver;
+mod transformer;
 
+pub use crate::public::loader::*;
+pub use crate::public::reporter::*;
 pub use crate::public::resolver::*;
+pub use crate::public::transformer::*;
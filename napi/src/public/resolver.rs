@@ -1,14 +1,173 @@
 /*
   This is the common interface for "Resolver" plugins
 */
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
 
+use serde::Deserialize;
+use serde::Serialize;
+
 pub trait Resolver: Sync + Send + Debug {
   fn resolve(
     &self,
     from_path: &Path,
     specifier: &str,
-  ) -> Option<PathBuf>;
+    options: &ResolveOptions,
+  ) -> Result<ResolveResult, ResolveError>;
+}
+
+// Describes how a specifier is being imported
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResolveOptions {
+  pub kind: ImportKind,
+  pub environment: Environment,
+  // Package "exports" conditions to match, e.g. "import", "development"
+  pub conditions: Vec<String>,
+  // Arbitrary data passed through to resolvers untouched
+  pub meta: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportKind {
+  // import x from 'specifier'
+  #[default]
+  Import,
+  // require('specifier')
+  Require,
+  // import('specifier')
+  DynamicImport,
+  // @import 'specifier';
+  CssImport,
+  // url('specifier'), new URL('specifier', import.meta.url)
+  Url,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Environment {
+  #[default]
+  Browser,
+  WebWorker,
+  Node,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResolveResult {
+  pub resolution: Resolution,
+  // False when the module is known to be free of side effects
+  pub side_effects: bool,
+  pub module_type: Option<ModuleType>,
+  // Query string carried along with the resolved path, "?raw"
+  pub query: Option<String>,
+  // Files whose modification or deletion would change this result
+  pub invalidate_on_file_change: Vec<PathBuf>,
+  // Files that do not exist yet but would change this result if created
+  pub invalidate_on_file_create: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Resolution {
+  // The resolver has no answer for this specifier
+  #[default]
+  Unresolved,
+  Path(PathBuf),
+  // The specifier should be left as-is and not bundled
+  External,
+  // The specifier names a runtime builtin, "fs" or "node:fs"
+  Builtin(String),
+  // A module that only exists in memory, its content comes from a Loader
+  Virtual(VirtualId),
+}
+
+// What a Loader is asked to load, either a file or a virtual module
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResolvedId {
+  Path(PathBuf),
+  Virtual(VirtualId),
+}
+
+// A module without a file, e.g. "virtual:routes" has the namespace
+// "virtual" and the id "routes"
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VirtualId {
+  pub namespace: String,
+  pub id: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleType {
+  Esm,
+  Cjs,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResolveError {
+  // The resolver ran and reported that resolution failed
+  Failed(String),
+  // The resolver could not be reached, e.g. a Node.js worker went away
+  Unreachable(String),
+}
+
+impl ResolveResult {
+  pub fn path(file_path: PathBuf) -> Self {
+    Self {
+      resolution: Resolution::Path(file_path),
+      ..Default::default()
+    }
+  }
+}
+
+impl Resolution {
+  // The module to load, if the specifier resolved to one
+  pub fn resolved_id(&self) -> Option<ResolvedId> {
+    match self {
+      Resolution::Path(file_path) => Some(ResolvedId::Path(file_path.clone())),
+      Resolution::Virtual(virtual_id) => Some(ResolvedId::Virtual(virtual_id.clone())),
+      _ => None,
+    }
+  }
+}
+
+impl Default for ResolveResult {
+  fn default() -> Self {
+    Self {
+      resolution: Resolution::Unresolved,
+      side_effects: true,
+      module_type: None,
+      query: None,
+      invalidate_on_file_change: vec![],
+      invalidate_on_file_create: vec![],
+    }
+  }
+}
+
+impl Display for ResolvedId {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      ResolvedId::Path(file_path) => write!(f, "{}", file_path.display()),
+      ResolvedId::Virtual(virtual_id) => write!(f, "{}:{}", virtual_id.namespace, virtual_id.id),
+    }
+  }
 }
+
+impl Display for ResolveError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      ResolveError::Failed(message) => write!(f, "Resolution failed: {}", message),
+      ResolveError::Unreachable(message) => write!(f, "Resolver unreachable: {}", message),
+    }
+  }
+}
+
+impl std::error::Error for ResolveError {}
@@ -0,0 +1,76 @@
+/*
+  This is the common interface for "Transformer" plugins
+*/
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::ImportKind;
+
+pub trait Transformer: Sync + Send + Debug {
+  fn transform(
+    &self,
+    input: &TransformInput,
+  ) -> Result<TransformOutput, TransformError>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TransformInput {
+  pub file_path: PathBuf,
+  pub code: String,
+  pub source_map: Option<String>,
+  // Arbitrary data passed through to transformers untouched
+  pub options: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TransformOutput {
+  pub code: String,
+  pub source_map: Option<String>,
+  // Modules the transformed code imports
+  pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Dependency {
+  pub specifier: String,
+  #[serde(default)]
+  pub kind: ImportKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransformError {
+  // The transformer ran and reported that it failed
+  Failed(String),
+  // The transformer could not be reached, e.g. a Node.js worker went away
+  Unreachable(String),
+}
+
+impl TransformOutput {
+  // The input passed through untouched
+  pub fn unchanged(input: &TransformInput) -> Self {
+    Self {
+      code: input.code.clone(),
+      source_map: input.source_map.clone(),
+      dependencies: vec![],
+    }
+  }
+}
+
+impl Display for TransformError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      TransformError::Failed(message) => write!(f, "Transform failed: {}", message),
+      TransformError::Unreachable(message) => write!(f, "Transformer unreachable: {}", message),
+    }
+  }
+}
+
+impl std::error::Error for TransformError {}
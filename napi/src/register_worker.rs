@@ -6,8 +6,16 @@
 
   It facilitates sending the worker "on ready" event and handles 
   communications with the worker, casting types to/from JavaScript land
+
+  Plugins are loaded here, required from the config file, and kept out
+  of reach of JavaScript, requests refer to them by their handle
 */
-use std::path::PathBuf;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
@@ -18,11 +26,18 @@ use neon::prelude::*;
 
 use once_cell::sync::Lazy;
 
+use crate::js_serde::from_js;
+use crate::js_serde::to_js;
+use crate::worker_farm::LifecycleHook;
+use crate::worker_farm::LoadResolverRequest;
+use crate::worker_farm::PluginError;
+use crate::worker_farm::PluginExports;
+use crate::worker_farm::PluginHandle;
 use crate::worker_farm::PluginRequest;
 use crate::worker_farm::PluginResponse;
-use crate::worker_farm::RunResolverResponse;
 
-pub type WorkerSender = Sender<(PluginRequest, tokio::sync::oneshot::Sender<PluginResponse>)>;
+// Workers send their slot in the farm along with the sender
+pub type WorkerLoaded = (Sender<(usize, WorkerSender)>, Option<Receiver<(usize, WorkerSender)>>);
 
 /*
   This is state that is shared between the Node workers and facilitates
@@ -30,71 +45,759 @@ pub type WorkerSender = Sender<(PluginRequest, tokio::sync::oneshot::Sender<Plug
 
   Global static variables are shared between multiple 
   instances of the same napi module
+
+  Workers register under the id of the farm they belong to so several
+  farms can exist at once, or be disposed and built again. A farm
+  keeps listening for registrations while it is alive so workers
+  that are restarted can join it again
 */
-pub static WORKER_LOADED: Lazy<
-  Arc<Mutex<(Sender<WorkerSender>, Option<Receiver<WorkerSender>>)>>,
-> = Lazy::new(|| {
-  let (tx, rx) = channel::<WorkerSender>();
-  Arc::new(Mutex::new((tx, Some(rx))))
-});
+pub static WORKER_LOADED: Lazy<Arc<Mutex<HashMap<String, WorkerLoaded>>>> =
+  Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
 
-pub fn register_worker(mut cx: FunctionContext) -> JsResult<JsUndefined> {
-  let ctx_load_resolver = cx.global().get_value(&mut cx, "load_resolver").unwrap();
-  let ctx_load_resolver: Handle<JsFunction> = ctx_load_resolver.downcast(&mut cx).unwrap();
+// Whichever of the farm and its first worker arrives first creates the channel
+fn worker_loaded<R>(
+  farm_id: &str,
+  f: impl FnOnce(&mut WorkerLoaded) -> R,
+) -> R {
+  let mut worker_loaded = WORKER_LOADED.lock().unwrap();
+  let entry = worker_loaded.entry(farm_id.to_string()).or_insert_with(|| {
+    let (tx, rx) = channel::<(usize, WorkerSender)>();
+    (tx, Some(rx))
+  });
+  return f(entry);
+}
 
-  let ctx_resolvers = cx.global().get_value(&mut cx, "resolvers").unwrap();
-  let ctx_resolvers: Handle<JsObject> = ctx_resolvers.downcast(&mut cx).unwrap();
+// Returns None when a farm with this id is already waiting for workers
+pub fn take_worker_loaded(farm_id: &str) -> Option<Receiver<(usize, WorkerSender)>> {
+  return worker_loaded(farm_id, |(_, rx)| rx.take());
+}
 
-  let (tx_call, rx_call) =
-    channel::<(PluginRequest, tokio::sync::oneshot::Sender<PluginResponse>)>();
+// Ends the farm's registrations, workers registering later under this
+// id go to the next farm built with it
+pub fn remove_worker_loaded(farm_id: &str) {
+  WORKER_LOADED.lock().unwrap().remove(farm_id);
+}
+
+/*
+  Schedules requests onto a worker's event loop. The worker is never
+  blocked waiting for work so timers, I/O and Promises inside
+  plugins keep running between requests
+*/
+#[derive(Clone, Debug)]
+pub struct WorkerSender {
+  // Shared rather than cloned, dropping a cloned Channel after the
+  // worker has exited panics
+  channel: Arc<Channel>,
+}
 
-  WORKER_LOADED.lock().unwrap().0.send(tx_call).unwrap();
+// The id of requests that get no response
+const NOTIFY_ID: usize = usize::MAX;
 
-  while let Ok((req, res)) = rx_call.recv() {
-    match req {
-      PluginRequest::LoadResolver(req) => {
-        let js_obj = cx.empty_object();
-        let js_specifier = cx.string(req.specifier);
-        js_obj.set(&mut cx, "specifier", js_specifier).unwrap();
+impl WorkerSender {
+  pub fn send(
+    &self,
+    id: usize,
+    req: PluginRequest,
+    res: tokio::sync::oneshot::Sender<PluginResponse>,
+  ) {
+    self.schedule(id, req, Some(res));
+  }
 
-        ctx_load_resolver
-          .call_with(&mut cx)
-          .arg(js_obj)
-          .apply::<JsUndefined, FunctionContext>(&mut cx)?;
+  // Nothing waits on notifications, the worker only logs their errors
+  pub fn notify(
+    &self,
+    req: PluginRequest,
+  ) {
+    self.schedule(NOTIFY_ID, req, None);
+  }
 
-        res.send(PluginResponse::LoadResolver).unwrap();
+  fn schedule(
+    &self,
+    id: usize,
+    req: PluginRequest,
+    res: Option<tokio::sync::oneshot::Sender<PluginResponse>>,
+  ) {
+    // Fails when the worker has exited, dropping res lets the caller know
+    let sent = self.channel.try_send(move |mut cx| {
+      if EXITING.with(Cell::get) {
+        return Ok(());
       }
-      PluginRequest::RunResolver(key, req) => {
-        let ctx_resolver_fn = ctx_resolvers.get_value(&mut cx, key.as_str()).unwrap();
-        let ctx_resolver_fn: Handle<JsFunction> = ctx_resolver_fn.downcast(&mut cx).unwrap();
-
-        let js_obj = cx.empty_object();
-        let js_from_path = cx.string(req.from_path.to_str().unwrap());
-        let js_specifier = cx.string(req.specifier);
-
-        js_obj.set(&mut cx, "from_path", js_from_path).unwrap();
-        js_obj.set(&mut cx, "specifier", js_specifier).unwrap();
-
-        let result = ctx_resolver_fn
-          .call_with(&mut cx)
-          .arg(js_obj)
-          .apply::<JsObject, FunctionContext>(&mut cx)?;
-
-        let file_path: Handle<JsString> = result
-          .get_value(&mut cx, "file_path")
-          .unwrap()
-          .downcast(&mut cx)
-          .unwrap();
-        let file_path = PathBuf::from(file_path.value(&mut cx));
-
-        res
-          .send(PluginResponse::RunResolver(RunResolverResponse {
-            file_path,
-          }))
-          .unwrap();
+      if let Some(res) = res {
+        IN_FLIGHT.with(|in_flight| in_flight.borrow_mut().insert(id, res));
+      }
+
+      // Exceptions are sent back as errors so the worker stays
+      // alive for the next request
+      if let Err(exception) = cx.try_catch(|cx| handle_request(cx, id, req)) {
+        // Plugins calling process.exit stop the worker mid request,
+        // JavaScript can't be run any more
+        if EXITING.with(Cell::get) {
+          return Ok(());
+        }
+        let error = to_plugin_error(&mut cx, exception);
+        respond(id, PluginResponse::Error(error));
       }
+      return Ok(());
+    });
+    sent.ok();
+  }
+}
+
+thread_local! {
+  // Requests this worker has received but not answered yet, a worker
+  // can have many in flight while plugins wait on Promises
+  static IN_FLIGHT: RefCell<HashMap<usize, tokio::sync::oneshot::Sender<PluginResponse>>> =
+    RefCell::new(HashMap::new());
+
+  // Set once the worker has started exiting
+  static EXITING: Cell<bool> = const { Cell::new(false) };
+
+  /*
+    Each worker has its own thread so these are per worker, like
+    instance data (which neon 0.10 doesn't expose). The require of
+    lib/worker.js loads node:module to require plugins, which are kept
+    with a name for errors
+  */
+  static REQUIRE: RefCell<Option<Root<JsFunction>>> = const { RefCell::new(None) };
+  static SLOT: Cell<usize> = const { Cell::new(0) };
+  static PLUGINS: RefCell<HashMap<PluginHandle, (String, Root<JsObject>)>> =
+    RefCell::new(HashMap::new());
+}
+
+fn respond(
+  id: usize,
+  response: PluginResponse,
+) {
+  if id == NOTIFY_ID {
+    if let PluginResponse::Error(error) = response {
+      eprintln!("{}", error);
     }
+    return;
   }
 
+  let Some(res) = IN_FLIGHT.with(|in_flight| in_flight.borrow_mut().remove(&id)) else {
+    return;
+  };
+  // The caller may have stopped waiting
+  res.send(response).ok();
+}
+
+// register_worker(farm_id, slot, require)
+pub fn register_worker(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let arg0: Handle<JsString> = cx.argument(0)?;
+  let arg1: Handle<JsNumber> = cx.argument(1)?;
+  let arg2: Handle<JsFunction> = cx.argument(2)?;
+  let farm_id = arg0.value(&mut cx);
+  let slot = arg1.value(&mut cx) as usize;
+
+  let require = arg2.root(&mut cx);
+  REQUIRE.with(|cell| cell.replace(Some(require)));
+  SLOT.with(|cell| cell.set(slot));
+
+  // The channel keeps the worker's event loop alive after this returns,
+  // the worker can exit once the farm drops it
+  let channel = cx.channel();
+
+  let on_exit = JsFunction::new(&mut cx, |mut cx| {
+    EXITING.with(|exiting| exiting.set(true));
+    return Ok(cx.undefined());
+  })?;
+  let process: Handle<JsObject> = cx.global().get(&mut cx, "process")?;
+  let process_on: Handle<JsFunction> = process.get(&mut cx, "on")?;
+  let event = cx.string("exit");
+  process_on
+    .call_with(&cx)
+    .this(process)
+    .arg(event)
+    .arg(on_exit)
+    .exec(&mut cx)?;
+
+  // The farm may have stopped listening while the worker was starting
+  worker_loaded(&farm_id, |(tx, _)| tx.send((slot, WorkerSender { channel: Arc::new(channel) })).ok());
+
   return Ok(cx.undefined());
 }
+
+fn handle_request(
+  cx: &mut TaskContext,
+  id: usize,
+  req: PluginRequest,
+) -> NeonResult<()> {
+  match req {
+    PluginRequest::LoadResolver(handle, req) => {
+      load_resolver(cx, id, handle, req)?;
+    }
+    PluginRequest::RunResolver(handle, req) => {
+      let js_req = to_js(cx, &req)?;
+      run_plugin(cx, id, handle, js_req, RunKind::Resolver)?;
+    }
+    PluginRequest::LoadTransformer(handle, req) => {
+      load_plugin(cx, handle, format!("Transformer \"{}\"", req.specifier), &req.specifier, &req.config_path)?;
+      setup_plugin(cx, id, handle, &serde_json::Value::Null, PluginResponse::LoadTransformer)?;
+    }
+    PluginRequest::RunTransformer(handle, mut req) => {
+      // The code is moved into JS rather than copied by to_js
+      let code = std::mem::take(&mut req.code).into_js(cx);
+      let js_req = to_js(cx, &req)?.downcast_or_throw::<JsObject, _>(cx)?;
+      js_req.set(cx, "code", code)?;
+      run_plugin(cx, id, handle, js_req.upcast(), RunKind::Transformer)?;
+    }
+    PluginRequest::LoadLoader(handle, req) => {
+      load_plugin(cx, handle, format!("Loader \"{}\"", req.specifier), &req.specifier, &req.config_path)?;
+      setup_plugin(cx, id, handle, &serde_json::Value::Null, PluginResponse::LoadLoader)?;
+    }
+    PluginRequest::RunLoader(handle, req) => {
+      let js_req = to_js(cx, &req)?;
+      run_plugin(cx, id, handle, js_req, RunKind::Loader)?;
+    }
+    PluginRequest::LoadReporter(handle, req) => {
+      load_plugin(cx, handle, format!("Reporter \"{}\"", req.specifier), &req.specifier, &req.config_path)?;
+      setup_plugin(cx, id, handle, &serde_json::Value::Null, PluginResponse::LoadReporter)?;
+    }
+    PluginRequest::RunReporter(handle, event) => {
+      let js_event = to_js(cx, &event)?;
+      run_plugin(cx, id, handle, js_event, RunKind::Reporter)?;
+    }
+    PluginRequest::RunHook(hook) => {
+      run_hook(cx, id, hook)?;
+    }
+  }
+
+  return Ok(());
+}
+
+// Requires the plugin and keeps its exports under the handle
+fn load_plugin(
+  cx: &mut TaskContext,
+  handle: PluginHandle,
+  name: String,
+  specifier: &str,
+  config_path: &Path,
+) -> NeonResult<()> {
+  let (exports, _) = require_plugin(cx, specifier, config_path)?;
+  return insert_plugin(cx, handle, name, exports);
+}
+
+// Resolvers may export a factory that makes the resolver from the
+// options in the config, exports.factory = (options) => resolver.
+// The factory may return a Promise, the load is answered once it settles
+fn load_resolver(
+  cx: &mut TaskContext,
+  id: usize,
+  handle: PluginHandle,
+  req: LoadResolverRequest,
+) -> NeonResult<()> {
+  let name = format!("Resolver \"{}\"", req.specifier);
+  let (exports, esm) = require_plugin(cx, &req.specifier, &req.config_path)?;
+
+  let factory = match exports.downcast::<JsObject, _>(cx) {
+    Ok(exports) => exports.get_value(cx, "factory")?,
+    Err(_) => cx.undefined().upcast(),
+  };
+  let Ok(factory) = factory.downcast::<JsFunction, _>(cx) else {
+    return add_resolver(cx, id, handle, name, exports, &req.options, esm, false);
+  };
+
+  let options = match req.options.is_null() {
+    true => cx.empty_object().upcast(),
+    false => to_js(cx, &req.options)?,
+  };
+  let resolver = factory
+    .call_with(cx)
+    .arg(options)
+    .apply::<JsValue, _>(cx)?;
+
+  let Some(then) = get_then(cx, resolver)? else {
+    return add_resolver(cx, id, handle, name, resolver, &req.options, esm, true);
+  };
+
+  let on_fulfilled = JsFunction::new(cx, move |mut cx| {
+    let resolver = cx.argument::<JsValue>(0)?;
+    let loaded = cx.try_catch(|cx| add_resolver(cx, id, handle, name.clone(), resolver, &req.options, esm, true));
+    match loaded {
+      Ok(()) => {}
+      Err(exception) => {
+        let error = to_plugin_error(&mut cx, exception);
+        respond(id, PluginResponse::Error(error));
+      }
+    }
+    return Ok(cx.undefined());
+  })?;
+
+  let on_rejected = JsFunction::new(cx, move |mut cx| {
+    let reason = cx.argument::<JsValue>(0)?;
+    let error = to_plugin_error(&mut cx, reason);
+    respond(id, PluginResponse::Error(error));
+    return Ok(cx.undefined());
+  })?;
+
+  then
+    .call_with(cx)
+    .this(resolver)
+    .arg(on_fulfilled)
+    .arg(on_rejected)
+    .exec(cx)?;
+
+  return Ok(());
+}
+
+// The load response describes the resolver so the host can check it,
+// resolvers it is going to turn down are not kept or set up
+#[allow(clippy::too_many_arguments)]
+fn add_resolver<'a, C: Context<'a>>(
+  cx: &mut C,
+  id: usize,
+  handle: PluginHandle,
+  name: String,
+  resolver: Handle<JsValue>,
+  options: &serde_json::Value,
+  esm: bool,
+  factory: bool,
+) -> NeonResult<()> {
+  let exports = describe_plugin(cx, resolver, "resolve", esm, factory)?;
+  if exports.check("resolve").is_err() {
+    respond(id, PluginResponse::LoadResolver(exports));
+    return Ok(());
+  }
+
+  insert_plugin(cx, handle, name, resolver)?;
+  return setup_plugin(cx, id, handle, options, PluginResponse::LoadResolver(exports));
+}
+
+// What a plugin looks like, see PluginExports
+fn describe_plugin<'a, C: Context<'a>>(
+  cx: &mut C,
+  plugin: Handle<JsValue>,
+  method: &str,
+  esm: bool,
+  factory: bool,
+) -> NeonResult<PluginExports> {
+  let mut members = BTreeMap::<String, String>::new();
+
+  let object = match plugin.downcast::<JsFunction, _>(cx) {
+    Ok(plugin) => Some(plugin.upcast::<JsObject>()),
+    Err(_) => plugin.downcast::<JsObject, _>(cx).ok(),
+  };
+  if let Some(object) = object {
+    for name in [method, "setup", "buildStart", "buildEnd", "teardown", "invalidate"] {
+      let member = object.get_value(cx, name)?;
+      if !member.is_a::<JsUndefined, _>(cx) {
+        members.insert(name.to_string(), type_of(cx, member).to_string());
+      }
+    }
+  }
+
+  return Ok(PluginExports {
+    esm,
+    factory,
+    type_of: type_of(cx, plugin).to_string(),
+    members,
+  });
+}
+
+// typeof, except null is "null"
+fn type_of<'a, C: Context<'a>>(
+  cx: &mut C,
+  value: Handle<JsValue>,
+) -> &'static str {
+  if value.is_a::<JsFunction, _>(cx) {
+    return "function";
+  }
+  if value.is_a::<JsString, _>(cx) {
+    return "string";
+  }
+  if value.is_a::<JsNumber, _>(cx) {
+    return "number";
+  }
+  if value.is_a::<JsBoolean, _>(cx) {
+    return "boolean";
+  }
+  if value.is_a::<JsUndefined, _>(cx) {
+    return "undefined";
+  }
+  if value.is_a::<JsNull, _>(cx) {
+    return "null";
+  }
+  if value.is_a::<JsObject, _>(cx) {
+    return "object";
+  }
+  return "value";
+}
+
+/*
+  Plugins export a function, or an object with that function named
+  after what it does (resolve, transform, load or report) and any of
+  these optional lifecycle hooks, which may return Promises:
+    setup(ctx)    once the plugin is loaded, ctx is { worker_id, options }
+    buildStart()  before each build
+    buildEnd()    after each build
+    teardown()    before the workers exit
+  The load is answered once setup is done
+*/
+fn setup_plugin<'a, C: Context<'a>>(
+  cx: &mut C,
+  id: usize,
+  handle: PluginHandle,
+  options: &serde_json::Value,
+  response: PluginResponse,
+) -> NeonResult<()> {
+  let plugin = PLUGINS.with(|plugins| plugins.borrow().get(&handle).map(|(_, plugin)| plugin.to_inner(cx)));
+  let Some(plugin) = plugin else {
+    return cx.throw_error(format!("No plugin is loaded for {:?}", handle));
+  };
+
+  let setup = plugin.get_value(cx, "setup")?;
+  let Ok(setup) = setup.downcast::<JsFunction, _>(cx) else {
+    respond(id, response);
+    return Ok(());
+  };
+
+  let ctx = cx.empty_object();
+  let worker_id = cx.number(SLOT.with(Cell::get) as f64);
+  ctx.set(cx, "worker_id", worker_id)?;
+  let options = match options.is_null() {
+    true => cx.empty_object().upcast(),
+    false => to_js(cx, options)?,
+  };
+  ctx.set(cx, "options", options)?;
+
+  let result = setup
+    .call_with(cx)
+    .this(plugin)
+    .arg(ctx)
+    .apply::<JsValue, _>(cx)?;
+
+  let Some(then) = get_then(cx, result)? else {
+    respond(id, response);
+    return Ok(());
+  };
+
+  let on_fulfilled = JsFunction::new(cx, move |mut cx| {
+    respond(id, response.clone());
+    return Ok(cx.undefined());
+  })?;
+
+  let on_rejected = JsFunction::new(cx, move |mut cx| {
+    let reason = cx.argument::<JsValue>(0)?;
+    let error = to_plugin_error(&mut cx, reason);
+    respond(id, PluginResponse::Error(error));
+    return Ok(cx.undefined());
+  })?;
+
+  then
+    .call_with(cx)
+    .this(result)
+    .arg(on_fulfilled)
+    .arg(on_rejected)
+    .exec(cx)?;
+
+  return Ok(());
+}
+
+// Runs the hook of every plugin at once, a hook failing doesn't stop
+// the others, the errors are sent back
+fn run_hook(
+  cx: &mut TaskContext,
+  id: usize,
+  hook: LifecycleHook,
+) -> NeonResult<()> {
+  let plugins = PLUGINS.with(|plugins| {
+    let plugins = plugins.borrow();
+    return plugins
+      .values()
+      .map(|(name, plugin)| (name.clone(), plugin.to_inner(cx)))
+      .collect::<Vec<_>>();
+  });
+
+  // Hooks that throw straight away don't make it into the Promises
+  let mut errors = Vec::<String>::new();
+  let mut names = Vec::<String>::new();
+  let results = cx.empty_array();
+
+  for (name, plugin) in plugins {
+    let hook_fn = plugin.get_value(cx, hook.js_name())?;
+    let Ok(hook_fn) = hook_fn.downcast::<JsFunction, _>(cx) else {
+      continue;
+    };
+
+    match cx.try_catch(|cx| hook_fn.call_with(cx).this(plugin).apply::<JsValue, _>(cx)) {
+      Ok(result) => {
+        results.set(cx, names.len() as u32, result)?;
+        names.push(name);
+      }
+      Err(exception) => {
+        let error = to_plugin_error(cx, exception);
+        errors.push(format!("{} failed in {}: {}", name, hook.js_name(), error.message));
+      }
+    }
+  }
+
+  let promise: Handle<JsFunction> = cx.global().get(cx, "Promise")?;
+  let all_settled: Handle<JsFunction> = promise.get(cx, "allSettled")?;
+  let settled = all_settled
+    .call_with(cx)
+    .this(promise)
+    .arg(results)
+    .apply::<JsObject, _>(cx)?;
+
+  let on_settled = JsFunction::new(cx, move |mut cx| {
+    let outcomes = cx.argument::<JsArray>(0)?.to_vec(&mut cx)?;
+    let mut errors = errors.clone();
+
+    for (outcome, name) in outcomes.into_iter().zip(&names) {
+      let outcome: Handle<JsObject> = outcome.downcast_or_throw(&mut cx)?;
+      let status: Handle<JsString> = outcome.get(&mut cx, "status")?;
+      if status.value(&mut cx) == "rejected" {
+        let reason = outcome.get_value(&mut cx, "reason")?;
+        let error = to_plugin_error(&mut cx, reason);
+        errors.push(format!("{} failed in {}: {}", name, hook.js_name(), error.message));
+      }
+    }
+
+    respond(id, PluginResponse::RunHook(errors));
+    return Ok(cx.undefined());
+  })?;
+
+  let then: Handle<JsFunction> = settled.get(cx, "then")?;
+  then
+    .call_with(cx)
+    .this(settled)
+    .arg(on_settled)
+    .exec(cx)?;
+
+  return Ok(());
+}
+
+// Plugins are required from the config file, so packages are found in
+// the node_modules next to it rather than next to lib/worker.js.
+// ES modules are used through their default export when they have one,
+// require() gives back their namespace. Returns whether it was used
+fn require_plugin<'a>(
+  cx: &mut TaskContext<'a>,
+  specifier: &str,
+  config_path: &Path,
+) -> NeonResult<(Handle<'a, JsValue>, bool)> {
+  let require = REQUIRE.with(|cell| cell.borrow().as_ref().map(|require| require.to_inner(cx)));
+  let Some(require) = require else {
+    return cx.throw_error("register_worker has not been called on this worker");
+  };
+
+  // createRequire needs an absolute path
+  let config_path = env::current_dir().unwrap_or_default().join(config_path);
+  let module_name = cx.string("node:module");
+  // node:module exports the Module class
+  let module = require
+    .call_with(cx)
+    .arg(module_name)
+    .apply::<JsFunction, _>(cx)?;
+  let create_require: Handle<JsFunction> = module.get(cx, "createRequire")?;
+  let config_path = cx.string(config_path.to_string_lossy());
+  let require = create_require
+    .call_with(cx)
+    .arg(config_path)
+    .apply::<JsFunction, _>(cx)?;
+
+  let specifier = cx.string(specifier);
+  let exports = require
+    .call_with(cx)
+    .arg(specifier)
+    .apply::<JsValue, _>(cx)?;
+
+  let Ok(object) = exports.downcast::<JsObject, _>(cx) else {
+    return Ok((exports, false));
+  };
+
+  // Transpiled modules set __esModule, namespaces are "[object Module]"
+  let es_module = object.get_value(cx, "__esModule")?;
+  let is_module = match es_module.downcast::<JsBoolean, _>(cx) {
+    Ok(es_module) => es_module.value(cx),
+    Err(_) => {
+      let object_ctor: Handle<JsFunction> = cx.global().get(cx, "Object")?;
+      let prototype: Handle<JsObject> = object_ctor.get(cx, "prototype")?;
+      let to_string: Handle<JsFunction> = prototype.get(cx, "toString")?;
+      let tag = to_string
+        .call_with(cx)
+        .this(object)
+        .apply::<JsString, _>(cx)?;
+      tag.value(cx) == "[object Module]"
+    }
+  };
+
+  let default = object.get_value(cx, "default")?;
+  if is_module && !default.is_a::<JsUndefined, _>(cx) {
+    return Ok((default, true));
+  }
+  return Ok((exports, false));
+}
+
+fn insert_plugin<'a, C: Context<'a>>(
+  cx: &mut C,
+  handle: PluginHandle,
+  name: String,
+  plugin: Handle<JsValue>,
+) -> NeonResult<()> {
+  let plugin: Handle<JsObject> = match plugin.downcast::<JsFunction, _>(cx) {
+    Ok(plugin) => plugin.upcast(),
+    Err(_) => match plugin.downcast::<JsObject, _>(cx) {
+      Ok(plugin) => plugin,
+      Err(_) => return cx.throw_type_error(format!("{} does not export a function", name)),
+    },
+  };
+  let plugin = plugin.root(cx);
+  PLUGINS.with(|plugins| plugins.borrow_mut().insert(handle, (name, plugin)));
+
+  return Ok(());
+}
+
+// Which response the value a plugin returns is read into
+#[derive(Clone, Copy)]
+enum RunKind {
+  Resolver,
+  Transformer,
+  Loader,
+  Reporter,
+}
+
+impl RunKind {
+  // The name of the method in the object form of the plugin
+  fn js_name(&self) -> &'static str {
+    match self {
+      RunKind::Resolver => "resolve",
+      RunKind::Transformer => "transform",
+      RunKind::Loader => "load",
+      RunKind::Reporter => "report",
+    }
+  }
+}
+
+// Calls the plugin, the response is sent once its result is ready
+fn run_plugin(
+  cx: &mut TaskContext,
+  id: usize,
+  handle: PluginHandle,
+  js_req: Handle<JsValue>,
+  kind: RunKind,
+) -> NeonResult<()> {
+  let plugin = PLUGINS.with(|plugins| {
+    let plugins = plugins.borrow();
+    let (name, exports) = plugins.get(&handle)?;
+    return Some((name.clone(), exports.to_inner(cx)));
+  });
+  let Some((name, exports)) = plugin else {
+    return cx.throw_error(format!("No plugin is loaded for {:?}", handle));
+  };
+
+  // Plugins in the object form have the function as a method
+  let plugin_fn = match exports.downcast::<JsFunction, _>(cx) {
+    Ok(plugin_fn) => plugin_fn,
+    Err(_) => {
+      let method = exports.get_value(cx, kind.js_name())?;
+      let Ok(method) = method.downcast::<JsFunction, _>(cx) else {
+        return cx.throw_type_error(format!(
+          "{} does not export a function or an object with a \"{}\" method",
+          name,
+          kind.js_name()
+        ));
+      };
+      method
+    }
+  };
+
+  let result = plugin_fn
+    .call_with(cx)
+    .this(exports)
+    .arg(js_req)
+    .apply::<JsValue, _>(cx)?;
+
+  let Some(then) = get_then(cx, result)? else {
+    let response = to_response(cx, kind, result)?;
+    respond(id, response);
+    return Ok(());
+  };
+
+  // Async plugins, the response is sent once the Promise settles
+  let on_fulfilled = JsFunction::new(cx, move |mut cx| {
+    let value = cx.argument::<JsValue>(0)?;
+    match cx.try_catch(|cx| to_response(cx, kind, value)) {
+      Ok(response) => respond(id, response),
+      Err(exception) => {
+        let error = to_plugin_error(&mut cx, exception);
+        respond(id, PluginResponse::Error(error));
+      }
+    }
+    return Ok(cx.undefined());
+  })?;
+
+  let on_rejected = JsFunction::new(cx, move |mut cx| {
+    let reason = cx.argument::<JsValue>(0)?;
+    let error = to_plugin_error(&mut cx, reason);
+    respond(id, PluginResponse::Error(error));
+    return Ok(cx.undefined());
+  })?;
+
+  then
+    .call_with(cx)
+    .this(result)
+    .arg(on_fulfilled)
+    .arg(on_rejected)
+    .exec(cx)?;
+
+  return Ok(());
+}
+
+// Returns the "then" method of Promises and other thenables
+fn get_then<'a, C: Context<'a>>(
+  cx: &mut C,
+  value: Handle<JsValue>,
+) -> NeonResult<Option<Handle<'a, JsFunction>>> {
+  let Ok(value) = value.downcast::<JsObject, _>(cx) else {
+    return Ok(None);
+  };
+  let then = value.get_value(cx, "then")?;
+  return Ok(then.downcast::<JsFunction, _>(cx).ok());
+}
+
+// JS plugins return null to pass on to the next resolver or loader, or
+// to leave the code as it is
+fn to_response<'a, C: Context<'a>>(
+  cx: &mut C,
+  kind: RunKind,
+  result: Handle<'a, JsValue>,
+) -> NeonResult<PluginResponse> {
+  return match kind {
+    RunKind::Resolver => Ok(PluginResponse::RunResolver(from_js(cx, result)?)),
+    RunKind::Transformer => Ok(PluginResponse::RunTransformer(from_js(cx, result)?)),
+    RunKind::Loader => Ok(PluginResponse::RunLoader(from_js(cx, result)?)),
+    // Whatever reporters return is ignored
+    RunKind::Reporter => Ok(PluginResponse::RunReporter),
+  };
+}
+
+// Uses the "message" and "stack" of Error objects, anything else
+// that is thrown is stringified
+fn to_plugin_error<'a, C: Context<'a>>(
+  cx: &mut C,
+  exception: Handle<JsValue>,
+) -> PluginError {
+  let error = cx.try_catch(|cx| {
+    if let Ok(exception) = exception.downcast::<JsObject, _>(cx) {
+      let message = exception.get_value(cx, "message")?;
+      if let Ok(message) = message.downcast::<JsString, _>(cx) {
+        let stack = exception.get_value(cx, "stack")?;
+        return Ok(PluginError {
+          message: message.value(cx),
+          stack: stack.downcast::<JsString, _>(cx).ok().map(|stack| stack.value(cx)),
+        });
+      }
+    }
+    return Ok(PluginError {
+      message: exception.to_string(cx)?.value(cx),
+      stack: None,
+    });
+  });
+
+  return error.unwrap_or_else(|_| PluginError {
+    message: "Plugin threw a value that could not be read".to_string(),
+    stack: None,
+  });
+}
@@ -0,0 +1,460 @@
+/*
+  Runs on main
+
+  A Rust-owned object that JavaScript holds on to (lib/resolver_host.js
+  wraps it in a class). It owns the worker farm, the resolver pipeline
+  and a thread pool the work runs on, so calls from JavaScript return
+  a Promise straight away and never block the main event loop.
+
+  The plugins and the default worker count come from the closest
+  mach.config.json. The workers are started from here (see
+  spawn_workers.rs), waiting for them to connect and loading plugins
+  happens on the thread pool, calls made before that is done wait for it
+*/
+use std::env;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use neon::prelude::*;
+use serde::Serialize;
+use tokio::runtime::Runtime;
+use tokio::sync::OnceCell;
+
+use crate::config::Backend;
+use crate::config::MachConfig;
+use crate::config::PluginSet;
+use crate::graph::GraphBuilder;
+use crate::graph::ModuleGraph;
+use crate::pipeline::PipelineError;
+use crate::pipeline::PipelineResult;
+use crate::public::LoadOutput;
+use crate::public::Loader;
+use crate::public::Reporter;
+use crate::public::ReporterEvent;
+use crate::public::Resolution;
+use crate::public::ResolveOptions;
+use crate::public::TransformInput;
+use crate::public::TransformOutput;
+use crate::public::Transformer;
+use crate::js_serde::from_js;
+use crate::js_serde::to_js;
+use crate::spawn_workers::spawn_workers;
+use crate::worker_farm::LifecycleHook;
+use crate::worker_farm::NodeWorkerFarm;
+
+// How long to wait for all the Node workers to call register_worker
+const REGISTRATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type BoxedResolverHost = JsBox<ResolverHost>;
+
+pub struct ResolverHost {
+  runtime: Arc<Runtime>,
+  inner: Arc<HostInner>,
+}
+
+// The parts of the host the thread pool works with
+struct HostInner {
+  state: OnceCell<Result<HostState, String>>,
+  worker_farm: Arc<NodeWorkerFarm>,
+  config: MachConfig,
+  concurrent: bool,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ResolveManyItem {
+  Ok(PipelineResult),
+  Err { error: String },
+}
+
+#[derive(Clone)]
+struct HostState {
+  plugins: Arc<PluginSet>,
+}
+
+impl Finalize for ResolverHost {
+  fn finalize<'a, C: Context<'a>>(
+    self,
+    _cx: &mut C,
+  ) {
+    // Don't block the JS thread waiting on work that is still running
+    if let Ok(runtime) = Arc::try_unwrap(self.runtime) {
+      runtime.shutdown_background();
+    }
+  }
+}
+
+impl HostInner {
+  // Resolves to the connected farm and pipeline, or why they failed
+  async fn state(&self) -> Result<HostState, String> {
+    let worker_farm = self.worker_farm.clone();
+    let config = self.config.clone();
+    let concurrent = self.concurrent;
+    let state = self
+      .state
+      .get_or_init(|| async move {
+        tokio::task::spawn_blocking(move || create_state(worker_farm, config, concurrent))
+          .await
+          .unwrap_or_else(|error| Err(error.to_string()))
+      })
+      .await;
+    return state.clone();
+  }
+
+  // Runs a lifecycle hook of the plugins once they are loaded,
+  // returns the errors the hooks threw
+  async fn run_hook(
+    &self,
+    hook: LifecycleHook,
+  ) -> Vec<String> {
+    if self.state().await.is_err() {
+      return vec![];
+    }
+    return self.worker_farm.run_hook(hook).await;
+  }
+}
+
+fn create_state(
+  worker_farm: Arc<NodeWorkerFarm>,
+  config: MachConfig,
+  concurrent: bool,
+) -> Result<HostState, String> {
+  // Wait for the Node workers to connect
+  worker_farm
+    .wait_ready(REGISTRATION_TIMEOUT)
+    .map_err(|error| error.to_string())?;
+
+  let plugins = PluginSet::load(&config, &worker_farm, concurrent).map_err(|error| error.to_string())?;
+
+  return Ok(HostState {
+    plugins: Arc::new(plugins),
+  });
+}
+
+// host_new(farm_id, worker_count | null, concurrent, max_in_flight, spawn_worker)
+// Without a worker count the one in the config is used
+pub fn host_new(mut cx: FunctionContext) -> JsResult<BoxedResolverHost> {
+  let arg0: Handle<JsString> = cx.argument(0)?;
+  let arg1: Handle<JsValue> = cx.argument(1)?;
+  let arg2: Handle<JsBoolean> = cx.argument(2)?;
+  let arg3: Handle<JsNumber> = cx.argument(3)?;
+  let arg4: Handle<JsFunction> = cx.argument(4)?;
+
+  let config = env::current_dir()
+    .map_err(|error| error.to_string())
+    .and_then(|cwd| MachConfig::find(&cwd).map_err(|error| error.to_string()))
+    .and_then(|config| {
+      config
+        .check_backend(Backend::Napi)
+        .map(|_| config)
+        .map_err(|error| error.to_string())
+    });
+  let config = match config {
+    Ok(config) => config,
+    Err(error) => return cx.throw_error(error),
+  };
+
+  let farm_id = arg0.value(&mut cx);
+  let worker_count = match arg1.downcast::<JsNumber, _>(&mut cx) {
+    Ok(worker_count) => worker_count.value(&mut cx) as usize,
+    Err(_) => config.workers.unwrap_or(4),
+  };
+  let concurrent = arg2.value(&mut cx);
+  let max_in_flight = arg3.value(&mut cx) as usize;
+
+  let worker_farm = match NodeWorkerFarm::new(&farm_id, worker_count, max_in_flight) {
+    Ok(worker_farm) => worker_farm,
+    Err(error) => return cx.throw_error(error.to_string()),
+  };
+  spawn_workers(&mut cx, &worker_farm, arg4)?;
+
+  let runtime = match Runtime::new() {
+    Ok(runtime) => Arc::new(runtime),
+    Err(error) => return cx.throw_error(error.to_string()),
+  };
+
+  let host = ResolverHost {
+    runtime,
+    inner: Arc::new(HostInner {
+      state: OnceCell::new(),
+      worker_farm,
+      config,
+      concurrent,
+    }),
+  };
+
+  // Start connecting now rather than on the first call
+  let inner = host.inner.clone();
+  host.runtime.spawn(async move {
+    inner.state().await.ok();
+  });
+
+  return Ok(cx.boxed(host));
+}
+
+// host_resolve(host, from_path, specifier) -> Promise<PipelineResult>
+pub fn host_resolve(mut cx: FunctionContext) -> JsResult<JsPromise> {
+  let host = cx.argument::<BoxedResolverHost>(0)?;
+  let from_path = PathBuf::from(cx.argument::<JsString>(1)?.value(&mut cx));
+  let specifier = cx.argument::<JsString>(2)?.value(&mut cx);
+
+  let channel = cx.channel();
+  let (deferred, promise) = cx.promise();
+  let inner = host.inner.clone();
+
+  host.runtime.spawn(async move {
+    let result = resolve(&inner, from_path, specifier).await;
+
+    deferred.settle_with(&channel, move |mut cx| match result {
+      Ok(result) => to_js(&mut cx, &result),
+      Err(error) => cx.throw_error(error),
+    });
+  });
+
+  return Ok(promise);
+}
+
+// host_resolve_many(host, [[from_path, specifier], ...]) -> Promise<Array>
+// Each item is a PipelineResult or { error } when that resolution failed,
+// the batch is reported as a build with a warning for each failure
+pub fn host_resolve_many(mut cx: FunctionContext) -> JsResult<JsPromise> {
+  let host = cx.argument::<BoxedResolverHost>(0)?;
+  let js_requests = cx.argument::<JsArray>(1)?.to_vec(&mut cx)?;
+
+  let mut requests = Vec::<(PathBuf, String)>::new();
+  for js_request in js_requests {
+    let js_request: Handle<JsArray> = js_request.downcast_or_throw(&mut cx)?;
+    let from_path: Handle<JsString> = js_request.get(&mut cx, 0)?;
+    let specifier: Handle<JsString> = js_request.get(&mut cx, 1)?;
+    requests.push((PathBuf::from(from_path.value(&mut cx)), specifier.value(&mut cx)));
+  }
+
+  let channel = cx.channel();
+  let (deferred, promise) = cx.promise();
+  let inner = host.inner.clone();
+
+  host.runtime.spawn(async move {
+    let reporter = inner.state().await.ok().map(|state| state.plugins);
+    let report = |event: ReporterEvent| {
+      if let Some(reporter) = &reporter {
+        reporter.report(&event);
+      }
+    };
+    let completed = AtomicUsize::new(0);
+
+    for message in inner.run_hook(LifecycleHook::BuildStart).await {
+      report(ReporterEvent::Warning { message, module: None });
+    }
+    report(ReporterEvent::BuildStart);
+    let results = futures::future::join_all(requests.into_iter().map(|(from_path, specifier)| async {
+      let result = resolve(&inner, from_path, specifier.clone()).await;
+      match &result {
+        Ok(_) => report(ReporterEvent::Progress {
+          module: specifier,
+          completed: completed.fetch_add(1, Ordering::Relaxed) + 1,
+        }),
+        Err(error) => report(ReporterEvent::Warning {
+          message: error.clone(),
+          module: Some(specifier),
+        }),
+      }
+      return result;
+    }))
+    .await;
+    for message in inner.run_hook(LifecycleHook::BuildEnd).await {
+      report(ReporterEvent::Warning { message, module: None });
+    }
+    report(ReporterEvent::BuildEnd {
+      success: results.iter().all(|result| result.is_ok()),
+    });
+
+    let results = results
+      .into_iter()
+      .map(|result| match result {
+        Ok(result) => ResolveManyItem::Ok(result),
+        Err(error) => ResolveManyItem::Err { error },
+      })
+      .collect::<Vec<ResolveManyItem>>();
+
+    deferred.settle_with(&channel, move |mut cx| to_js(&mut cx, &results));
+  });
+
+  return Ok(promise);
+}
+
+// host_transform(host, file_path, code) -> Promise<TransformOutput>
+pub fn host_transform(mut cx: FunctionContext) -> JsResult<JsPromise> {
+  let host = cx.argument::<BoxedResolverHost>(0)?;
+  let file_path = PathBuf::from(cx.argument::<JsString>(1)?.value(&mut cx));
+  let code = cx.argument::<JsString>(2)?.value(&mut cx);
+
+  let channel = cx.channel();
+  let (deferred, promise) = cx.promise();
+  let inner = host.inner.clone();
+
+  host.runtime.spawn(async move {
+    let input = TransformInput {
+      file_path,
+      code,
+      ..Default::default()
+    };
+    let result = transform(&inner, input).await;
+
+    deferred.settle_with(&channel, move |mut cx| match result {
+      Ok(output) => to_js(&mut cx, &output),
+      Err(error) => cx.throw_error(error),
+    });
+  });
+
+  return Ok(promise);
+}
+
+// host_load(host, resolution) -> Promise<LoadOutput | null>
+// Takes the resolution of a PipelineResult, null when no loader claims it
+pub fn host_load(mut cx: FunctionContext) -> JsResult<JsPromise> {
+  let host = cx.argument::<BoxedResolverHost>(0)?;
+  let resolution = cx.argument::<JsValue>(1)?;
+  let resolution: Resolution = from_js(&mut cx, resolution)?;
+
+  let channel = cx.channel();
+  let (deferred, promise) = cx.promise();
+  let inner = host.inner.clone();
+
+  host.runtime.spawn(async move {
+    let result = load(&inner, resolution).await;
+
+    deferred.settle_with(&channel, move |mut cx| match result {
+      Ok(output) => to_js(&mut cx, &output),
+      Err(error) => cx.throw_error(error),
+    });
+  });
+
+  return Ok(promise);
+}
+
+// host_build_graph(host, [entry, ...]) -> Promise<ModuleGraph>
+pub fn host_build_graph(mut cx: FunctionContext) -> JsResult<JsPromise> {
+  let host = cx.argument::<BoxedResolverHost>(0)?;
+  let js_entries = cx.argument::<JsArray>(1)?.to_vec(&mut cx)?;
+
+  let mut entries = Vec::<PathBuf>::new();
+  for js_entry in js_entries {
+    let js_entry: Handle<JsString> = js_entry.downcast_or_throw(&mut cx)?;
+    entries.push(PathBuf::from(js_entry.value(&mut cx)));
+  }
+
+  let channel = cx.channel();
+  let (deferred, promise) = cx.promise();
+  let inner = host.inner.clone();
+
+  host.runtime.spawn(async move {
+    let result = build_graph(&inner, entries).await;
+
+    deferred.settle_with(&channel, move |mut cx| match result {
+      Ok(graph) => to_js(&mut cx, &graph),
+      Err(error) => cx.throw_error(error),
+    });
+  });
+
+  return Ok(promise);
+}
+
+// host_close(host) -> Promise<undefined>
+// Lets go of the workers, calls made afterwards fail
+pub fn host_close(mut cx: FunctionContext) -> JsResult<JsPromise> {
+  let host = cx.argument::<BoxedResolverHost>(0)?;
+
+  let channel = cx.channel();
+  let (deferred, promise) = cx.promise();
+  let inner = host.inner.clone();
+
+  host.runtime.spawn(async move {
+    // Lets plugins clean up before the workers are let go of
+    for error in inner.run_hook(LifecycleHook::Teardown).await {
+      eprintln!("{}", error);
+    }
+    inner.worker_farm.dispose();
+    deferred.settle_with(&channel, |mut cx| Ok(cx.undefined()));
+  });
+
+  return Ok(promise);
+}
+
+async fn resolve(
+  inner: &HostInner,
+  from_path: PathBuf,
+  specifier: String,
+) -> Result<PipelineResult, String> {
+  let state = inner.state().await?;
+
+  // Resolvers block while they wait on the Node workers
+  let result = tokio::task::spawn_blocking(move || {
+    state
+      .plugins
+      .resolvers
+      .resolve(&from_path, &specifier, &ResolveOptions::default())
+  })
+  .await
+  .map_err(|error| error.to_string())?;
+
+  return result.map_err(|error: PipelineError| error.to_string());
+}
+
+async fn transform(
+  inner: &HostInner,
+  input: TransformInput,
+) -> Result<TransformOutput, String> {
+  let state = inner.state().await?;
+
+  // Transformers block while they wait on the Node workers
+  let result = tokio::task::spawn_blocking(move || state.plugins.transform(&input))
+    .await
+    .map_err(|error| error.to_string())?;
+
+  return result.map_err(|error| error.to_string());
+}
+
+async fn load(
+  inner: &HostInner,
+  resolution: Resolution,
+) -> Result<Option<LoadOutput>, String> {
+  let Some(resolved_id) = resolution.resolved_id() else {
+    return Err(format!("{:?} does not refer to a module", resolution));
+  };
+  let state = inner.state().await?;
+
+  // Loaders block while they wait on the Node workers
+  let result = tokio::task::spawn_blocking(move || state.plugins.load(&resolved_id))
+    .await
+    .map_err(|error| error.to_string())?;
+
+  return result.map_err(|error| error.to_string());
+}
+
+async fn build_graph(
+  inner: &HostInner,
+  entries: Vec<PathBuf>,
+) -> Result<ModuleGraph, String> {
+  let state = inner.state().await?;
+  let plugins = state.plugins.clone();
+
+  for message in inner.run_hook(LifecycleHook::BuildStart).await {
+    plugins.report(&ReporterEvent::Warning { message, module: None });
+  }
+
+  // The graph builder resolves on threads of its own
+  let graph = tokio::task::spawn_blocking(move || {
+    GraphBuilder::new(&state.plugins.resolvers, ResolveOptions::default()).build(&entries)
+  })
+  .await
+  .map_err(|error| error.to_string());
+
+  for message in inner.run_hook(LifecycleHook::BuildEnd).await {
+    plugins.report(&ReporterEvent::Warning { message, module: None });
+  }
+
+  return graph;
+}
@@ -0,0 +1,69 @@
+/*
+  Runs on main
+
+  Starts the Node workers of a farm through the spawn_worker function
+  from lib/spawn_worker.js and starts a replacement when one exits
+  (unless the farm was disposed or the worker keeps exiting)
+*/
+use std::sync::Arc;
+use std::sync::Weak;
+
+use neon::prelude::*;
+
+use crate::worker_farm::NodeWorkerFarm;
+
+pub fn spawn_workers<'a, C: Context<'a>>(
+  cx: &mut C,
+  farm: &Arc<NodeWorkerFarm>,
+  spawn_worker: Handle<JsFunction>,
+) -> NeonResult<()> {
+  let spawn_worker = Arc::new(spawn_worker.root(cx));
+
+  for slot in 0..farm.worker_count() {
+    spawn(cx, Arc::downgrade(farm), spawn_worker.clone(), slot)?;
+  }
+  return Ok(());
+}
+
+// spawn_worker(farm_id, slot, on_exit(code, error))
+fn spawn<'a, C: Context<'a>>(
+  cx: &mut C,
+  farm: Weak<NodeWorkerFarm>,
+  spawn_worker: Arc<Root<JsFunction>>,
+  slot: usize,
+) -> NeonResult<()> {
+  let Some(farm_id) = farm.upgrade().map(|farm| farm.farm_id().to_string()) else {
+    return Ok(());
+  };
+
+  let on_exit_spawn_worker = spawn_worker.clone();
+  let on_exit = JsFunction::new(cx, move |mut cx| {
+    let code = cx.argument::<JsNumber>(0)?.value(&mut cx);
+    let error = cx.argument::<JsValue>(1)?;
+
+    let reason = match error.downcast::<JsString, _>(&mut cx) {
+      Ok(error) => error.value(&mut cx),
+      Err(_) => format!("exit code {}", code),
+    };
+
+    let Some(restart) = farm.upgrade().map(|farm| farm.worker_exited(slot, &reason)) else {
+      return Ok(cx.undefined());
+    };
+    if restart {
+      spawn(&mut cx, farm.clone(), on_exit_spawn_worker.clone(), slot)?;
+    }
+    return Ok(cx.undefined());
+  })?;
+
+  let farm_id = cx.string(farm_id);
+  let slot = cx.number(slot as f64);
+  spawn_worker
+    .to_inner(cx)
+    .call_with(cx)
+    .arg(farm_id)
+    .arg(slot)
+    .arg(on_exit)
+    .exec(cx)?;
+
+  return Ok(());
+}
@@ -1,32 +1,207 @@
 /*
   Types that go to/from Node workers
+
+  Large payloads such as file contents should use js_serde::Bytes,
+  they reach plugins as a Buffer rather than a string
 */
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::js_serde::Bytes;
+use crate::public::Dependency;
+use crate::public::ModuleType;
+use crate::public::ReporterEvent;
+use crate::public::ResolveOptions;
+use crate::public::VirtualId;
+
 #[derive(Clone, Debug)]
 pub enum PluginRequest {
-  LoadResolver(LoadResolverRequest),
-  RunResolver(String, RunResolverRequest),
+  LoadResolver(PluginHandle, LoadResolverRequest),
+  RunResolver(PluginHandle, RunResolverRequest),
+  LoadTransformer(PluginHandle, LoadTransformerRequest),
+  RunTransformer(PluginHandle, RunTransformerRequest),
+  LoadLoader(PluginHandle, LoadLoaderRequest),
+  RunLoader(PluginHandle, RunLoaderRequest),
+  LoadReporter(PluginHandle, LoadReporterRequest),
+  // Only sent as a notification, see NodeWorkerFarm::notify
+  RunReporter(PluginHandle, ReporterEvent),
+  // Runs the hook of every plugin the worker has loaded
+  RunHook(LifecycleHook),
+}
+
+// Refers to a loaded plugin, the same on every worker of a farm
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PluginHandle(usize);
+
+impl PluginHandle {
+  pub(super) fn new(id: usize) -> Self {
+    return Self(id);
+  }
 }
 
 #[derive(Clone, Debug)]
 pub enum PluginResponse {
-  LoadResolver,
-  RunResolver(RunResolverResponse),
+  LoadResolver(PluginExports),
+  RunResolver(Option<RunResolverResponse>),
+  LoadTransformer,
+  RunTransformer(Option<RunTransformerResponse>),
+  LoadLoader,
+  RunLoader(Option<RunLoaderResponse>),
+  LoadReporter,
+  RunReporter,
+  // The errors thrown by the hooks
+  RunHook(Vec<String>),
+  Error(PluginError),
+}
+
+/*
+  The lifecycle hooks JS plugins may export next to their main
+  function. setup(ctx) runs as part of loading a plugin, the rest
+  are run on every worker with NodeWorkerFarm::run_hook
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LifecycleHook {
+  BuildStart,
+  BuildEnd,
+  Teardown,
 }
 
+impl LifecycleHook {
+  // The name of the hook on the plugin object
+  pub fn js_name(&self) -> &'static str {
+    match self {
+      LifecycleHook::BuildStart => "buildStart",
+      LifecycleHook::BuildEnd => "buildEnd",
+      LifecycleHook::Teardown => "teardown",
+    }
+  }
+}
+
+// A JS exception or an invalid value returned by a plugin
 #[derive(Clone, Debug)]
+pub struct PluginError {
+  pub message: String,
+  pub stack: Option<String>,
+}
+
+impl std::fmt::Display for PluginError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    // The stack starts with the message
+    match &self.stack {
+      Some(stack) => write!(f, "{}", stack),
+      None => write!(f, "{}", self.message),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct LoadResolverRequest {
   pub specifier: String,
+  // Plugins named after a package are found from here, see MachConfig
+  pub config_path: PathBuf,
+  // Passed to the plugin's factory, if it exports one
+  pub options: serde_json::Value,
 }
 
-#[derive(Clone, Debug)]
+/*
+  What a JS plugin exports, checked by the proxy that loaded it
+  {
+    esm: boolean,      // the plugin is the default export of an ES module
+    factory: boolean,  // the plugin was returned by exports.factory(options)
+    type: string,      // typeof the plugin
+    members: {},       // typeof the method and hooks the plugin has
+  }
+*/
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PluginExports {
+  pub esm: bool,
+  pub factory: bool,
+  #[serde(rename = "type")]
+  pub type_of: String,
+  pub members: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RunResolverRequest {
   pub from_path: PathBuf,
   pub specifier: String,
+  pub options: ResolveOptions,
 }
 
-#[derive(Clone, Debug)]
+// Every field is optional on the JS side
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
 pub struct RunResolverResponse {
+  pub file_path: Option<PathBuf>,
+  pub virtual_id: Option<VirtualId>,
+  pub external: bool,
+  pub builtin: Option<String>,
+  pub side_effects: Option<bool>,
+  pub module_type: Option<ModuleType>,
+  pub query: Option<String>,
+  pub invalidate_on_file_change: Vec<PathBuf>,
+  pub invalidate_on_file_create: Vec<PathBuf>,
+  pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LoadTransformerRequest {
+  pub specifier: String,
+  // Plugins named after a package are found from here, see MachConfig
+  pub config_path: PathBuf,
+}
+
+// The code is sent as a Buffer
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunTransformerRequest {
   pub file_path: PathBuf,
+  pub code: Bytes,
+  pub source_map: Option<String>,
+  pub options: BTreeMap<String, serde_json::Value>,
+}
+
+// The code can come back as a string or a Buffer
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct RunTransformerResponse {
+  pub code: Option<Bytes>,
+  pub source_map: Option<String>,
+  pub dependencies: Vec<Dependency>,
+  pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LoadLoaderRequest {
+  pub specifier: String,
+  // Plugins named after a package are found from here, see MachConfig
+  pub config_path: PathBuf,
+}
+
+// Only one of file_path and virtual_id is set
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunLoaderRequest {
+  pub file_path: Option<PathBuf>,
+  pub virtual_id: Option<VirtualId>,
+}
+
+// The code can come back as a string or a Buffer
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct RunLoaderResponse {
+  pub code: Option<Bytes>,
+  pub source_map: Option<String>,
+  pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LoadReporterRequest {
+  pub specifier: String,
+  // Plugins named after a package are found from here, see MachConfig
+  pub config_path: PathBuf,
 }
@@ -1,79 +1,440 @@
 /*
   Runs on main
-  
+
   This is an abstraction that provides a nice interface to talk
   to the Node workers from and handles load balancing between them
+
+  Each worker can have several requests in flight at once (up to
+  max_in_flight), which lets async plugins wait on I/O without
+  leaving the worker idle. Requests are tagged with an id that the
+  worker uses to match up its responses
+
+  Farms are identified by the farm id their workers register with.
+  Disposing a farm releases its workers and lets another farm be
+  built with the same id
+
+  Workers are started by spawn_workers.rs and hold a slot in the
+  farm. When one exits its in-flight requests fail and the worker
+  that replaces it gets the plugins loaded so far before taking
+  over the slot
 */
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Condvar;
 use std::sync::Mutex;
+use std::time::Duration;
 
+use tokio::sync::watch;
+use tokio::sync::Semaphore;
+
+use crate::register_worker::remove_worker_loaded;
+use crate::register_worker::take_worker_loaded;
 use crate::register_worker::WorkerSender;
-use crate::register_worker::WORKER_LOADED;
 
+use super::LifecycleHook;
+use super::PluginHandle;
 use super::PluginRequest;
 use super::PluginResponse;
 
+// Times in a row a worker may exit before registering before its
+// slot is given up on
+const MAX_FAILED_STARTS: usize = 3;
+
 #[derive(Debug)]
 pub struct NodeWorkerFarm {
+  farm_id: String,
+  max_in_flight: usize,
   send_to: Arc<Mutex<usize>>,
-  workers: Vec<WorkerSender>,
+  next_id: AtomicUsize,
+  next_plugin_handle: AtomicUsize,
+  // One per slot, None while the worker is starting
+  workers: Mutex<Vec<Option<NodeWorker>>>,
+  workers_changed: Condvar,
+  // Exits since each slot's worker last registered
+  failed_starts: Mutex<Vec<usize>>,
+  // Sent to every worker that joins after they were loaded
+  loaded: Mutex<Vec<PluginRequest>>,
+  disposed: AtomicBool,
+}
+
+#[derive(Clone, Debug)]
+struct NodeWorker {
+  sender: WorkerSender,
+  // One permit per request that may be in flight
+  permits: Arc<Semaphore>,
+  // Set to true once the worker has exited
+  exited: Arc<watch::Sender<bool>>,
+}
+
+#[derive(Debug)]
+pub enum NodeWorkerFarmError {
+  AlreadyExists(String),
+  RegistrationTimeout {
+    farm_id: String,
+    expected: usize,
+    registered: usize,
+  },
+}
+
+impl NodeWorker {
+  fn new(
+    sender: WorkerSender,
+    max_in_flight: usize,
+  ) -> Self {
+    return NodeWorker {
+      sender,
+      permits: Arc::new(Semaphore::new(max_in_flight.max(1))),
+      exited: Arc::new(watch::channel(false).0),
+    };
+  }
 }
 
 impl NodeWorkerFarm {
-  pub fn new(worker_count: usize) -> Self {
-    let onload = WORKER_LOADED.lock().unwrap().1.take().unwrap();
-    let mut workers = Vec::<WorkerSender>::new();
+  // Workers are started separately, see wait_ready
+  pub fn new(
+    farm_id: &str,
+    worker_count: usize,
+    max_in_flight: usize,
+  ) -> Result<Arc<Self>, NodeWorkerFarmError> {
+    let Some(onload) = take_worker_loaded(farm_id) else {
+      return Err(NodeWorkerFarmError::AlreadyExists(farm_id.to_string()));
+    };
+
+    let farm = Arc::new(NodeWorkerFarm {
+      farm_id: farm_id.to_string(),
+      max_in_flight,
+      send_to: Arc::new(Mutex::new(0)),
+      next_id: AtomicUsize::new(0),
+      next_plugin_handle: AtomicUsize::new(0),
+      workers: Mutex::new(vec![None; worker_count]),
+      workers_changed: Condvar::new(),
+      failed_starts: Mutex::new(vec![0; worker_count]),
+      loaded: Mutex::new(vec![]),
+      disposed: AtomicBool::new(false),
+    });
+
+    // Runs until the farm is disposed or dropped
+    let weak_farm = Arc::downgrade(&farm);
+    std::thread::spawn(move || {
+      while let Ok((slot, sender)) = onload.recv() {
+        let Some(farm) = weak_farm.upgrade() else {
+          break;
+        };
+        farm.worker_registered(slot, sender);
+      }
+    });
+
+    return Ok(farm);
+  }
+
+  pub fn farm_id(&self) -> &str {
+    return &self.farm_id;
+  }
+
+  pub fn worker_count(&self) -> usize {
+    return self.workers.lock().unwrap().len();
+  }
+
+  // For a plugin about to be loaded into the workers
+  pub fn new_plugin_handle(&self) -> PluginHandle {
+    return PluginHandle::new(self.next_plugin_handle.fetch_add(1, Ordering::Relaxed));
+  }
+
+  // Blocks until every slot has a worker
+  pub fn wait_ready(
+    &self,
+    timeout: Duration,
+  ) -> Result<(), NodeWorkerFarmError> {
+    let workers = self.workers.lock().unwrap();
+    let (workers, _) = self
+      .workers_changed
+      .wait_timeout_while(workers, timeout, |workers| {
+        !self.disposed.load(Ordering::Relaxed) && workers.iter().any(|worker| worker.is_none())
+      })
+      .unwrap();
 
-    for _ in 0..worker_count {
-      let tx_to_worker = onload.recv().unwrap();
-      workers.push(tx_to_worker)
+    let registered = workers.iter().filter(|worker| worker.is_some()).count();
+    if registered < workers.len() {
+      return Err(NodeWorkerFarmError::RegistrationTimeout {
+        farm_id: self.farm_id.clone(),
+        expected: workers.len(),
+        registered,
+      });
     }
+    return Ok(());
+  }
 
-    return NodeWorkerFarm {
-      workers,
-      send_to: Arc::new(Mutex::new(0)),
+  fn worker_registered(
+    &self,
+    slot: usize,
+    sender: WorkerSender,
+  ) {
+    if self.disposed.load(Ordering::Relaxed) || slot >= self.worker_count() {
+      return;
+    }
+    let worker = NodeWorker::new(sender, self.max_in_flight);
+
+    // Plugins are replayed without holding the lock so load_all is not
+    // stuck behind the worker. Plugins loaded meanwhile are replayed on
+    // the next pass, the worker takes its slot once there are none left
+    // and load_all sends it everything after that
+    let mut replayed = 0;
+    loop {
+      let pending = {
+        let loaded = self.loaded.lock().unwrap();
+        if replayed == loaded.len() {
+          self.failed_starts.lock().unwrap()[slot] = 0;
+          self.workers.lock().unwrap()[slot] = Some(worker);
+          break;
+        }
+        loaded[replayed..].to_vec()
+      };
+
+      for req in pending {
+        let response = futures::executor::block_on(self.send_to_worker(&worker, req));
+        if let Ok(PluginResponse::Error(error)) = response {
+          eprintln!("Restarted worker {} could not load a plugin: {}", slot, error);
+        }
+        replayed += 1;
+      }
+    }
+    self.workers_changed.notify_all();
+  }
+
+  /*
+    Called when a worker has exited, fails the requests it was
+    handling. Returns whether a worker should be started in its place
+  */
+  pub fn worker_exited(
+    &self,
+    slot: usize,
+    reason: &str,
+  ) -> bool {
+    if let Some(worker) = self.workers.lock().unwrap().get_mut(slot).and_then(Option::take) {
+      worker.permits.close();
+      worker.exited.send_replace(true);
+    }
+    if self.disposed.load(Ordering::Relaxed) {
+      return false;
+    }
+
+    let mut failed_starts = self.failed_starts.lock().unwrap();
+    let Some(failed) = failed_starts.get_mut(slot) else {
+      return false;
     };
+    *failed += 1;
+    if *failed > MAX_FAILED_STARTS {
+      eprintln!(
+        "Worker {} of farm \"{}\" keeps exiting ({}), not restarting it",
+        slot, self.farm_id, reason
+      );
+      return false;
+    }
+
+    eprintln!(
+      "Worker {} of farm \"{}\" exited ({}), restarting it",
+      slot, self.farm_id, reason
+    );
+    return true;
+  }
+
+  // Fails pending and future requests and lets go of the workers so
+  // their event loops can finish
+  pub fn dispose(&self) {
+    self.disposed.store(true, Ordering::Relaxed);
+    remove_worker_loaded(&self.farm_id);
+
+    for worker in self.workers.lock().unwrap().iter_mut() {
+      if let Some(worker) = worker.take() {
+        worker.permits.close();
+      }
+    }
+    self.workers_changed.notify_all();
   }
 
-  pub fn send_all(
+  // Sends to every worker, workers started later get the request too
+  pub async fn load_all(
     &self,
     req: PluginRequest,
   ) -> Result<Vec<PluginResponse>, ()> {
-    let mut responses = Vec::<PluginResponse>::new();
+    let workers = {
+      let mut loaded = self.loaded.lock().unwrap();
+      loaded.push(req.clone());
+      self.current_workers()
+    };
+    return self.send_to_workers(workers, req).await;
+  }
 
-    for worker in &self.workers {
-      let (res, on_response) = tokio::sync::oneshot::channel::<PluginResponse>();
-      worker.send((req.clone(), res)).unwrap();
-      let Ok(response) = on_response.blocking_recv() else {
-        return Err(());
-      };
-      responses.push(response)
-    }
-    return Ok(responses);
+  pub fn load_all_blocking(
+    &self,
+    req: PluginRequest,
+  ) -> Result<Vec<PluginResponse>, ()> {
+    return futures::executor::block_on(self.load_all(req));
+  }
+
+  pub async fn send(
+    &self,
+    req: PluginRequest,
+  ) -> Result<PluginResponse, ()> {
+    let Some(worker) = self.next_worker() else {
+      return Err(());
+    };
+    return self.send_to_worker(&worker, req).await;
   }
 
   pub fn send_blocking(
     &self,
     req: PluginRequest,
   ) -> Result<PluginResponse, ()> {
-    // Round robin
-    let send_to = {
+    return futures::executor::block_on(self.send(req));
+  }
+
+  // Sends to the worker in the given slot without waiting for it, the
+  // worker handles these in the order they were sent. Fails when no
+  // worker is running in that slot
+  pub fn notify(
+    &self,
+    slot: usize,
+    req: PluginRequest,
+  ) -> Result<(), ()> {
+    let workers = self.workers.lock().unwrap();
+    let Some(Some(worker)) = workers.get(slot) else {
+      return Err(());
+    };
+    worker.sender.notify(req);
+    return Ok(());
+  }
+
+  // Runs the hook of every plugin on every worker and waits for them
+  // to finish, returns the errors the hooks threw
+  pub async fn run_hook(
+    &self,
+    hook: LifecycleHook,
+  ) -> Vec<String> {
+    let workers = self.current_workers();
+    let Ok(responses) = self.send_to_workers(workers, PluginRequest::RunHook(hook)).await else {
+      return vec![format!("No response from the workers running {}", hook.js_name())];
+    };
+
+    let mut errors = vec![];
+    for response in responses {
+      match response {
+        PluginResponse::RunHook(hook_errors) => errors.extend(hook_errors),
+        PluginResponse::Error(error) => errors.push(error.to_string()),
+        response => errors.push(format!(
+          "Unexpected response from the workers running {}: {:?}",
+          hook.js_name(),
+          response
+        )),
+      }
+    }
+    return errors;
+  }
+
+  fn current_workers(&self) -> Vec<NodeWorker> {
+    let workers = self.workers.lock().unwrap();
+    return workers.iter().flatten().cloned().collect();
+  }
+
+  async fn send_to_workers(
+    &self,
+    workers: Vec<NodeWorker>,
+    req: PluginRequest,
+  ) -> Result<Vec<PluginResponse>, ()> {
+    let requests = workers
+      .iter()
+      .map(|worker| self.send_to_worker(worker, req.clone()));
+
+    return futures::future::join_all(requests).await.into_iter().collect();
+  }
+
+  async fn send_to_worker(
+    &self,
+    worker: &NodeWorker,
+    req: PluginRequest,
+  ) -> Result<PluginResponse, ()> {
+    // Waits while the worker is at its limit
+    let Ok(_permit) = worker.permits.acquire().await else {
+      return Err(());
+    };
+
+    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+    let (res, on_response) = tokio::sync::oneshot::channel::<PluginResponse>();
+    let mut exited = worker.exited.subscribe();
+    worker.sender.send(id, req, res);
+
+    // The worker can exit before it answers
+    tokio::select! {
+      response = on_response => response.map_err(|_| ()),
+      _ = exited.wait_for(|exited| *exited) => Err(()),
+    }
+  }
+
+  // Round robin, skipping over workers that are starting or at their
+  // limit unless all of them are
+  fn next_worker(&self) -> Option<NodeWorker> {
+    let workers = self.workers.lock().unwrap();
+    if workers.iter().all(|worker| worker.is_none()) {
+      return None;
+    }
+
+    let start = {
       let mut send_to = self.send_to.lock().unwrap();
 
-      if *send_to >= self.workers.len() {
+      if *send_to >= workers.len() {
         *send_to = 0;
       }
 
-      let send_index = (*send_to).clone();
+      let send_index = *send_to;
       *send_to += 1;
       send_index
     };
 
-    let (res, on_response) = tokio::sync::oneshot::channel::<PluginResponse>();
-    self.workers[send_to].send((req, res)).unwrap();
-    let Ok(response) = on_response.blocking_recv() else {
-      return Err(());
-    };
-    return Ok(response);
+    let mut fallback = None;
+    for i in 0..workers.len() {
+      let Some(worker) = &workers[(start + i) % workers.len()] else {
+        continue;
+      };
+      if worker.permits.available_permits() > 0 {
+        return Some(worker.clone());
+      }
+      fallback = fallback.or(Some(worker));
+    }
+    return fallback.cloned();
+  }
+}
+
+impl Drop for NodeWorkerFarm {
+  fn drop(&mut self) {
+    // Stops listening for registrations
+    if !self.disposed.load(Ordering::Relaxed) {
+      remove_worker_loaded(&self.farm_id);
+    }
   }
 }
+
+impl std::fmt::Display for NodeWorkerFarmError {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      NodeWorkerFarmError::AlreadyExists(farm_id) => {
+        write!(f, "A worker farm with the id \"{}\" already exists", farm_id)
+      }
+      NodeWorkerFarmError::RegistrationTimeout {
+        farm_id,
+        expected,
+        registered,
+      } => write!(
+        f,
+        "Timed out waiting for workers of farm \"{}\" to register ({} of {} registered)",
+        farm_id, registered, expected
+      ),
+    }
+  }
+}
+
+impl std::error::Error for NodeWorkerFarmError {}
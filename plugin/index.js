@@ -1,5 +1,20 @@
-module.exports = function resolve({ from_path, specifier }) {
-  return {
-    file_path: `${from_path}/${specifier}`
+const path = require('node:path')
+
+// Created for each entry in mach.config.json with that entry's options.
+// With a namespace only "<namespace>:" specifiers are claimed, as virtual
+// modules for plugin/loader.js, and everything else is passed on
+exports.factory = function factory({ namespace }) {
+  return function resolve({ from_path, specifier }) {
+    if (namespace === undefined) {
+      return {
+        file_path: path.join(from_path, specifier)
+      }
+    }
+    if (specifier.startsWith(`${namespace}:`)) {
+      return {
+        virtual_id: { namespace, id: specifier.slice(namespace.length + 1) }
+      }
+    }
+    return null
   }
 }
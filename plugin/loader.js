@@ -0,0 +1,9 @@
+module.exports = function load({ virtual_id }) {
+  // Files are left to other loaders
+  if (!virtual_id || virtual_id.namespace !== 'virtual') {
+    return null
+  }
+  return {
+    code: `export default ${JSON.stringify(virtual_id.id)};\n`
+  }
+}
@@ -0,0 +1,25 @@
+// Prints build events as they come in, the lifecycle hooks time each build
+module.exports = {
+  started: 0,
+
+  buildStart() {
+    this.started = Date.now()
+  },
+
+  report(event) {
+    switch (event.type) {
+      case 'build_start':
+        console.log('reporter: build started')
+        break
+      case 'progress':
+        console.log(`reporter: ${event.completed} done (${event.module})`)
+        break
+      case 'warning':
+        console.log(`reporter: warning ${event.message}`)
+        break
+      case 'build_end':
+        console.log(`reporter: build ${event.success ? 'succeeded' : 'failed'} in ${Date.now() - this.started}ms`)
+        break
+    }
+  },
+}
@@ -0,0 +1,16 @@
+const path = require('node:path')
+
+// Adds a banner with the file name and reports static imports as dependencies
+module.exports = function transform({ file_path, code }) {
+  code = String(code)
+
+  const dependencies = []
+  for (const [, specifier] of code.matchAll(/import\s[^'"]*['"]([^'"]+)['"]/g)) {
+    dependencies.push({ specifier, kind: 'import' })
+  }
+
+  return {
+    code: `/* ${path.basename(file_path)} */\n${code}`,
+    dependencies,
+  }
+}